@@ -0,0 +1,59 @@
+// Tokenizer throughput. Reads the Brown corpus from /brown/ (or the
+// directory in BROWN) when it is there, and otherwise tokenizes generated
+// text in the same tagged format. Run with `cargo bench`.
+
+extern crate graffiti;
+
+use std::env;
+use std::fs;
+use std::time::Instant;
+
+use graffiti::tokenizer::*;
+
+const SAMPLE: &str = "The/at Fulton/np-tl County/nn-tl Grand/jj-tl Jury/nn-tl said/vbd \
+    Friday/nr an/at investigation/nn of/in Atlanta's/np$ recent/jj primary/nn election/nn \
+    produced/vbd ``/`` no/at evidence/nn ''/'' that/cs any/dti irregularities/nns took/vbd \
+    place/nn ./. In 1959 ,/, 42/cd jurors/nns met/vbd ./.\n\n";
+
+fn corpus() -> (String, Vec<u8>) {
+    let dir = env::var("BROWN").unwrap_or_else(|_| String::from("/brown/"));
+    if let Ok(entries) = fs::read_dir(&dir) {
+        let mut text = Vec::new();
+        let mut paths: Vec<_> = entries.map(|e| e.unwrap().path()).collect();
+        paths.sort();
+        for path in paths {
+            text.extend(fs::read(path).unwrap());
+        }
+        return (format!("Brown corpus in {}", dir), text);
+    }
+    let mut text = Vec::new();
+    while text.len() < 6_000_000 {
+        text.extend_from_slice(SAMPLE.as_bytes());
+    }
+    (String::from("generated Brown-format text"), text)
+}
+
+fn bench(name: &str, text: &[u8], mut run: impl FnMut(&[u8]) -> usize) {
+    let mut best = f64::MAX;
+    let mut tokens = 0;
+    for _ in 0..5 {
+        let started = Instant::now();
+        tokens = run(text);
+        best = best.min(started.elapsed().as_secs_f64());
+    }
+    println!("{:<24} {:>8.1} MB/s  {:>9} tokens", name, text.len() as f64 / best / 1e6, tokens);
+}
+
+fn main() {
+    let (source, text) = corpus();
+    println!("{}, {:.1} MB, best of 5", source, text.len() as f64 / 1e6);
+
+    let mut brown = Tokenizer::standard("brown").unwrap();
+    bench("brown tokenize", &text, |t| brown.tokenize(t).len());
+    let brown = Tokenizer::standard("brown").unwrap();
+    bench("brown tokenize_ref", &text, |t| brown.tokenize_ref(t).len());
+    bench("brown stream", &text, |t| brown.stream(t).count());
+
+    let english = Tokenizer::standard("english").unwrap();
+    bench("english (regex) ref", &text, |t| english.tokenize_ref(t).len());
+}
@@ -0,0 +1,8 @@
+# Byte classes for ASCII letters, digits and whitespace, to include in
+# specs that add their own punctuation and transitions.
+
+[tokens]
+Alpha => 65..91
+Alpha => 97..123
+Number => 48..58
+Whitespace => 9,10,13,32
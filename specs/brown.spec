@@ -0,0 +1,31 @@
+# Brown corpus tagged text: words with their part of speech tag after a
+# slash, as in `Fulton/np-tl`. Words come out in the Alpha state and tags
+# in the Pos state.
+
+# The byte classes are those `Corpus::brown` has always used, so word
+# counts over the corpus stay as they were: bytes 91..96 count as letters,
+# the digit 9 and `.` are left unmapped.
+
+[tokens]
+Alpha => 65..123
+Number => 48..57
+Whitespace => 9,10,13,32
+Punctuation => 33..46
+Punctuation => 58..65
+Slash => 47
+
+[transitions]
+Start => Alpha => Alpha
+Start => Number => Number
+Start => Whitespace => Whitespace
+Start => Punctuation => Punctuation
+Start => Slash => Slash
+Slash => Slash => Slash
+Slash => Whitespace => Whitespace
+Slash => Alpha => Pos
+Alpha => Alpha | Number => Alpha
+Pos => Alpha => Pos
+Number => Number => Number
+Number => Alpha => Alpha
+Whitespace => Whitespace => Whitespace
+Punctuation => Punctuation => Punctuation
@@ -0,0 +1,8 @@
+# Comma separated values. Quoted fields may hold commas, newlines and
+# doubled quotes; the quotes stay in the token.
+
+[rules]
+Quoted = "([^"]|"")*"
+Field = [^,"\r\n]+
+Comma = ,
+Newline = \r?\n
@@ -0,0 +1,8 @@
+# Plain English text. Words keep their contractions and inner hyphens,
+# numbers their decimals and thousands separators.
+
+[rules]
+Word = [A-Za-z]+(['-][A-Za-z]+)*
+Number = \d+(,\d\d\d)*(\.\d+)?
+Punctuation = [!-/:-@\[-`{-~]
+Whitespace = \s+
@@ -0,0 +1,223 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use postagger::Tag;
+
+// Reads the tagged text of the Brown corpus. A file has a sentence per
+// line, as `word/tag` items separated by whitespace, with blank lines
+// between sentences:
+//
+//   The/at Fulton/np-tl County/nn-tl ... took/vbd place/nn ./.
+//
+// A word runs up to the last `/` of its item, so it can hold slashes of
+// its own. Files are named for their category and number, like `ca01`.
+
+// A tag as written in the corpus. Besides the base tag, such as `nn`, it
+// can say the word is foreign (`fw-nn`), in a title (`nn-tl`), headline
+// (`-hl`) or citation (`-nc`), possessive (`np$`) or negated (`bedz*`).
+// Contractions have a tag per part, joined by `+`, as in `pps+bez`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BrownTag {
+    text: String,
+}
+
+impl BrownTag {
+    pub fn new(text: &str) -> Self {
+        BrownTag { text: String::from(text) }
+    }
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+    pub fn parts(&self) -> Vec<&str> {
+        self.text.split('+').collect()
+    }
+    // The tag with its markers taken off: `nn` for `nn-tl`, `np` for
+    // `np$`. For a contraction, that of its first part.
+    pub fn base(&self) -> &str {
+        let mut tag = self.first();
+        if let Some(rest) = tag.strip_prefix("fw-") {
+            tag = rest;
+        }
+        while let Some(rest) = ["-tl", "-hl", "-nc"].iter().find_map(|s| tag.strip_suffix(s)) {
+            tag = rest;
+        }
+        // `*` alone is the tag of `not`
+        match tag.trim_end_matches(['$', '*']) {
+            "" => tag,
+            base => base
+        }
+    }
+    pub fn is_foreign(&self) -> bool {
+        self.text.starts_with("fw-")
+    }
+    pub fn is_title(&self) -> bool {
+        self.text.contains("-tl")
+    }
+    pub fn is_headline(&self) -> bool {
+        self.text.contains("-hl")
+    }
+    pub fn is_possessive(&self) -> bool {
+        self.first().contains('$')
+    }
+    pub fn is_negated(&self) -> bool {
+        self.text.contains('*')
+    }
+    // The tagger's coarse tag: nouns (`nn`, `np`, `nr`), verbs (`vb` and
+    // the forms of be, have and do) and adjectives (`jj`).
+    pub fn pos(&self) -> Option<Tag> {
+        let base = self.base();
+        let is = |prefixes: &[&str]| prefixes.iter().any(|p| base.starts_with(p));
+        if is(&["nn", "np", "nr"]) {
+            Some(Tag::N)
+        } else if is(&["vb", "be", "hv", "do"]) {
+            Some(Tag::VB)
+        } else if is(&["jj"]) {
+            Some(Tag::ADJ)
+        } else {
+            None
+        }
+    }
+    fn first(&self) -> &str {
+        self.text.split('+').next().unwrap_or("")
+    }
+}
+
+impl fmt::Display for BrownTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+pub type TaggedSentence = Vec<(Vec<u8>, BrownTag)>;
+
+// The category of a corpus file from its name: `ca` for `ca01`.
+pub fn category(file_name: &str) -> Option<&str> {
+    let b = file_name.as_bytes();
+    if b.len() == 4 && b[0] == b'c' && b[1].is_ascii_lowercase() && b[2..].iter().all(u8::is_ascii_digit) {
+        Some(&file_name[..2])
+    } else {
+        None
+    }
+}
+
+pub struct BrownReader<R> {
+    reader: R,
+    category: Option<String>,
+    line: usize,
+}
+
+impl BrownReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut reader = BrownReader::new(BufReader::new(File::open(path)?));
+        reader.category = path.file_name().and_then(|n| n.to_str()).and_then(category).map(String::from);
+        Ok(reader)
+    }
+}
+
+impl<R: BufRead> BrownReader<R> {
+    pub fn new(reader: R) -> Self {
+        BrownReader { reader, category: None, line: 0 }
+    }
+    // Set when the reader was opened from a file named for its category.
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+}
+
+impl<R: BufRead> Iterator for BrownReader<R> {
+    type Item = io::Result<TaggedSentence>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = Vec::new();
+            match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(_) => {},
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e))
+            }
+            self.line += 1;
+            let mut sentence = Vec::new();
+            for item in line.split(u8::is_ascii_whitespace).filter(|item| !item.is_empty()) {
+                let at = match item.iter().rposition(|&b| b == b'/') {
+                    Some(at) if at > 0 && at + 1 < item.len() => at,
+                    _ => {
+                        let message = format!("line {}: expected `word/tag`, found `{}`", self.line, String::from_utf8_lossy(item));
+                        return Some(Err(io::Error::new(io::ErrorKind::InvalidData, message)));
+                    }
+                };
+                sentence.push((item[..at].to_vec(), BrownTag::new(&String::from_utf8_lossy(&item[at + 1..]))));
+            }
+            if !sentence.is_empty() {
+                return Some(Ok(sentence));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn pair(word: &str, tag: &str) -> (Vec<u8>, BrownTag) {
+        (word.as_bytes().to_vec(), BrownTag::new(tag))
+    }
+
+    #[test]
+    fn read_tagged_sentences() {
+        let reader = BrownReader::open("./test_assets/brown/ca01").unwrap();
+
+        assert_eq!(reader.category(), Some("ca"));
+
+        let sentences = reader.collect::<io::Result<Vec<TaggedSentence>>>().unwrap();
+
+        assert_eq!(sentences.len(), 2);
+        assert_eq!(sentences[0].len(), 25);
+        assert_eq!(sentences[0][..3], [pair("The", "at"), pair("Fulton", "np-tl"), pair("County", "nn-tl")]);
+        assert_eq!(sentences[0][10], pair("Atlanta's", "np$"));
+        assert_eq!(sentences[0][15], pair("``", "``"));
+        assert_eq!(sentences[0][18], pair("''", "''"));
+        assert_eq!(sentences[0].last(), Some(&pair(".", ".")));
+        assert_eq!(sentences[1][5], pair("term-end", "nn"));
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut reader = BrownReader::new(&b"\tHe's/pps+bez 1/2/cd\n\n\tgone/vbn oops ./.\n"[..]);
+
+        assert_eq!(reader.category(), None);
+        assert_eq!(reader.next().unwrap().unwrap(), vec![pair("He's", "pps+bez"), pair("1/2", "cd")]);
+
+        let e = reader.next().unwrap().unwrap_err();
+
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "line 3: expected `word/tag`, found `oops`");
+        assert_eq!(category("CONTENTS"), None);
+    }
+
+    #[test]
+    fn tag_markers() {
+        let tag = BrownTag::new("nn-tl");
+
+        assert_eq!((tag.base(), tag.is_title(), tag.pos()), ("nn", true, Some(Tag::N)));
+
+        let tag = BrownTag::new("np$");
+
+        assert_eq!((tag.base(), tag.is_possessive(), tag.pos()), ("np", true, Some(Tag::N)));
+
+        let tag = BrownTag::new("pps+bez");
+
+        assert_eq!((tag.parts(), tag.base(), tag.pos()), (vec!["pps", "bez"], "pps", None));
+        assert_eq!(BrownTag::new("bedz*").pos(), Some(Tag::VB));
+        assert!(BrownTag::new("bedz*").is_negated());
+        assert_eq!(BrownTag::new("fw-jj-tl").base(), "jj");
+        assert_eq!(BrownTag::new("*").base(), "*");
+        assert_eq!(BrownTag::new("''").base(), "''");
+        assert_eq!(BrownTag::new(".").pos(), None);
+    }
+}
@@ -0,0 +1,182 @@
+use unicode_general_category::get_general_category;
+use unicode_script::{Script, UnicodeScript};
+
+use lexicon::*;
+use spec;
+use spec::SpecError;
+use tokenizer::*;
+
+// Character classes for Unicode token definitions. A definition is a comma
+// separated list of:
+//
+//   65            one code point, in decimal
+//   U+00E9        one code point, in hex
+//   65..91        a range, end exclusive like in ASCII definitions
+//   U+0391..U+03AA
+//   \p{L}         a general category, or a group of them by its first letter
+//   \p{Greek}     a script, by full or short name; \p{Script=Greek} and
+//                 \p{sc=Grek} also work
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CharSet {
+    Range(u32, u32),
+    Category(String),
+    Script(Script),
+}
+
+impl CharSet {
+    pub fn contains(&self, c: char) -> bool {
+        match *self {
+            CharSet::Range(start, end) => start <= c as u32 && (c as u32) < end,
+            CharSet::Category(ref abbr) => {
+                let cat = get_general_category(c).abbreviation();
+                if abbr.len() == 1 { cat.starts_with(abbr.as_str()) } else { cat == abbr }
+            },
+            CharSet::Script(script) => c.script() == script
+        }
+    }
+    pub fn parse(item: &str) -> Result<CharSet, String> {
+        if item.starts_with("\\p{") && item.ends_with('}') {
+            return parse_property(&item[3..item.len() - 1]);
+        }
+        if let Some(at) = item.find("..") {
+            let (start, end) = (parse_code_point(&item[..at])?, parse_code_point(&item[at + 2..])?);
+            if start >= end {
+                return Err(format!("empty range `{}`", item));
+            }
+            return Ok(CharSet::Range(start, end));
+        }
+        let c = parse_code_point(item)?;
+        Ok(CharSet::Range(c, c + 1))
+    }
+}
+
+fn parse_code_point(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let parsed = if s.starts_with("U+") || s.starts_with("u+") {
+        u32::from_str_radix(&s[2..], 16)
+    } else {
+        s.parse::<u32>()
+    };
+    match parsed {
+        Ok(c) if c <= 0x11_0000 => Ok(c),
+        _ => Err(format!("expected a code point, found `{}`", s))
+    }
+}
+
+const CATEGORIES: &[&str] = &[
+    "L", "Lu", "Ll", "Lt", "Lm", "Lo",
+    "M", "Mn", "Mc", "Me",
+    "N", "Nd", "Nl", "No",
+    "P", "Pc", "Pd", "Ps", "Pe", "Pi", "Pf", "Po",
+    "S", "Sm", "Sc", "Sk", "So",
+    "Z", "Zs", "Zl", "Zp",
+    "C", "Cc", "Cf", "Cs", "Co", "Cn",
+];
+
+fn parse_property(name: &str) -> Result<CharSet, String> {
+    let name = name.trim();
+    let script = match name.find('=') {
+        Some(at) => match name[..at].trim() {
+            "Script" | "sc" => name[at + 1..].trim(),
+            key => return Err(format!("unknown property `{}`", key))
+        },
+        None => {
+            if CATEGORIES.contains(&name) {
+                return Ok(CharSet::Category(String::from(name)));
+            }
+            name
+        }
+    };
+    Script::from_full_name(script)
+        .or_else(|| Script::from_short_name(script))
+        .map(CharSet::Script)
+        .ok_or_else(|| format!("unknown category or script `{}`", name))
+}
+
+// Token types by character. ASCII lookups are cached; other characters go
+// through the definitions, where a later one overrides an earlier one, as
+// with ASCII definitions.
+pub struct CharClasses {
+    rules: Vec<(Vec<CharSet>, TokenType)>,
+    ascii: Vec<TokenType>,
+}
+
+impl CharClasses {
+    pub fn new(rules: Vec<(Vec<CharSet>, TokenType)>) -> Self {
+        let mut classes = CharClasses {
+            rules,
+            ascii: Vec::new()
+        };
+        classes.ascii = (0..128u8).map(|b| classes.lookup(b as char)).collect();
+        classes
+    }
+    // Reads token definitions in the `Name => classes` format, interning
+    // their names in the tokenizer's symbol table.
+    pub fn compile(tokens: &str, symbols: &mut Lexicon) -> Result<Self, SpecError> {
+        let mut rules = Vec::new();
+        for def in spec::token_defs(tokens)? {
+            let mut sets = Vec::new();
+            for item in def.values.text.split(',') {
+                let item = def.values.sub(item).trim();
+                sets.push(CharSet::parse(item.text).map_err(|e| item.error(&e))?);
+            }
+            rules.push((sets, TokenType(symbols.intern(def.name.text.as_bytes()).0)));
+        }
+        Ok(CharClasses::new(rules))
+    }
+    // TokenType(0) if no definition covers the character.
+    pub fn classify(&self, c: char) -> TokenType {
+        if (c as u32) < 128 {
+            return self.ascii[c as usize].clone();
+        }
+        self.lookup(c)
+    }
+    fn lookup(&self, c: char) -> TokenType {
+        self.rules.iter()
+            .rev()
+            .find(|r| r.0.iter().any(|set| set.contains(c)))
+            .map_or(TokenType(0), |r| r.1.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_char_sets() {
+        assert_eq!(CharSet::parse("65..91").unwrap(), CharSet::Range(65, 91));
+        assert_eq!(CharSet::parse("U+00E9").unwrap(), CharSet::Range(0xe9, 0xea));
+        assert_eq!(CharSet::parse("\\p{Lu}").unwrap(), CharSet::Category(String::from("Lu")));
+        assert_eq!(CharSet::parse("\\p{Greek}").unwrap(), CharSet::Script(Script::Greek));
+        assert_eq!(CharSet::parse("\\p{sc=Cyrl}").unwrap(), CharSet::Script(Script::Cyrillic));
+        assert!(CharSet::parse("\\p{Klingon}").is_err());
+        assert!(CharSet::parse("U+zz").is_err());
+    }
+
+    #[test]
+    fn classify_chars() {
+        let mut symbols = Tokenizer::symbol_table();
+        let classes = CharClasses::compile("
+            Alpha => \\p{L}
+            Number => \\p{Nd}
+            Greek => \\p{Greek}
+            Space => 32, U+00A0
+            ", &mut symbols).unwrap();
+        let t = |name: &str| TokenType(symbols.get(name.as_bytes()).unwrap().0);
+
+        assert_eq!(classes.classify('a'), t("Alpha"));
+        assert_eq!(classes.classify('é'), t("Alpha"));
+        assert_eq!(classes.classify('λ'), t("Greek"));
+        assert_eq!(classes.classify('٣'), t("Number"));
+        assert_eq!(classes.classify('\u{a0}'), t("Space"));
+        assert_eq!(classes.classify('!'), TokenType(0));
+
+        let e = CharClasses::compile("Alpha => \\p{L}\nGreek => 32, \\p{Grek}, \\p{Elvish}", &mut symbols).err().unwrap();
+
+        assert_eq!((e.line, e.column), (2, 24));
+        assert_eq!(e.message, "unknown category or script `Elvish`");
+    }
+}
@@ -1,6 +1,12 @@
+use std::borrow::Cow;
 use std::fs;
-use utils::*;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::path::Path;
 
+use brown::*;
 use scanner::*;
 use tokenizer::*;
 
@@ -19,35 +25,7 @@ impl Corpus {
         }
     }
     pub fn brown() -> Self {
-        let tokens: &'static str = 
-            "
-            Alpha => 65..123
-            Number => 48..57
-            Whitespace => 9,10,13,32
-            Punctuation => 33..46
-            Punctuation => 58..65
-            Slash => 47
-            ";
-
-        let transitions: &'static str = 
-            "
-            Start => Alpha => Alpha
-            Start => Number => Number
-            Start => Whitespace => Whitespace
-            Start => Punctuation => Punctuation
-            Start => Slash => Slash
-            Slash => Slash => Slash
-            Slash => Whitespace => Whitespace
-            Slash => Alpha => Pos
-            Alpha => Alpha | Number => Alpha
-            Pos => Alpha => Pos
-            Number => Number => Number
-            Number => Alpha => Alpha
-            Whitespace => Whitespace => Whitespace
-            Punctuation => Punctuation => Punctuation
-            ";
-
-        let tokenizer = Tokenizer::new(&tokens, &transitions);
+        let tokenizer = Tokenizer::standard("brown").expect("the Brown spec is valid");
         let brown_path: &'static str = "/brown/";
 
         let scanners: Vec<Scanner> = Corpus::register_scanners(brown_path);
@@ -65,24 +43,56 @@ impl Corpus {
     pub fn get_scanners(&self) -> &Vec<Scanner> {
         &self.scanners
     }
-    pub fn words(&mut self, pos: usize) -> Vec<Vec<u8>> {
-        let contents = self.scanners[pos].scan().unwrap();
+    pub fn words(&mut self, pos: usize) -> io::Result<Vec<Vec<u8>>> {
+        let reader = self.scanners[pos].reader()?;
 
-        let tokens = self.tokenizer.tokenize(&contents);
+        self.stream_words(reader)
+    }
+    // The words of a stream, or the error that cut it short.
+    fn stream_words<R: BufRead>(&self, reader: R) -> io::Result<Vec<Vec<u8>>> {
+        let alpha = self.tokenizer.state("Alpha");
+        let mut stream = self.tokenizer.stream(reader);
+        let words = stream.by_ref().filter(|t| Some(&t.s) == alpha.as_ref()).map(|t| t.value).collect();
+
+        match stream.take_error() {
+            Some(e) => Err(e),
+            None => Ok(words)
+        }
+    }
+    // The sentences of a Brown file as word and tag pairs, which `words`
+    // leaves out.
+    pub fn tagged(&self, pos: usize) -> io::Result<BrownReader<BufReader<File>>> {
+        BrownReader::open(self.scanners[pos].get_file())
+    }
+    // The Brown category of a file, such as `ca`.
+    pub fn category(&self, pos: usize) -> Option<&str> {
+        Path::new(self.scanners[pos].get_file()).file_name().and_then(|n| n.to_str()).and_then(category)
+    }
+    pub fn contents(&self, pos: usize) -> io::Result<Vec<u8>> {
+        self.scanners[pos].scan()
+    }
+    // All the tokens of a file, not only its words. Under
+    // `Unmapped::Error`, unmapped input is an `InvalidData` error.
+    pub fn tokens(&self, pos: usize) -> io::Result<Vec<Token>> {
+        let contents = self.contents(pos)?;
+        let tokenized = self.tokenizer.try_tokenize_ref(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(tokenized.tokens.into_iter().map(Token::from).collect())
+    }
+    // The words of a file's contents, borrowed from them rather than copied.
+    // Unmapped input is an `InvalidData` error, as in `tokens`.
+    pub fn words_ref<'a>(&self, contents: &'a [u8]) -> io::Result<Vec<Cow<'a, [u8]>>> {
+        let alpha = self.tokenizer.state("Alpha");
+        let tokenized = self.tokenizer.try_tokenize_ref(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        let filtered_tokens = tokens.into_iter().filter(|f| f.s == State(utils::get_hash_val(b"Alpha"))).collect::<Vec<Token>>();
-        filtered_tokens.into_iter().map(|t| t.value).collect::<Vec<Vec<u8>>>()        
+        Ok(tokenized.tokens.into_iter().filter(|t| Some(&t.s) == alpha.as_ref()).map(|t| t.value).collect())
     }
-    pub fn allwords(&mut self) -> Vec<Vec<u8>> {
+    pub fn allwords(&mut self) -> io::Result<Vec<Vec<u8>>> {
         let mut all_tokens: Vec<Vec<u8>> = Vec::new();
         for s in &self.scanners {
-            let contents = s.scan().unwrap();
-            let tokens = self.tokenizer.tokenize(&contents);
-
-            let filtered_tokens = tokens.into_iter().filter(|f| f.s == State(utils::get_hash_val(b"Alpha"))).collect::<Vec<Token>>();
-            all_tokens.append(&mut filtered_tokens.into_iter().map(|t| t.value).collect::<Vec<Vec<u8>>>());
+            let reader = s.reader()?;
+            all_tokens.extend(self.stream_words(reader)?);
         };
-        all_tokens
+        Ok(all_tokens)
     }
 
 }
@@ -91,41 +101,11 @@ impl Corpus {
 #[cfg(test)]
 mod tests {
 
-    use std::str;
     use super::*;
-    use tokenizer::*;
-
-    static TOKENS: &'static str = 
-            "
-            Alpha => 65..123
-            Number => 48..57
-            Whitespace => 9,10,13,32
-            Punctuation => 33..46
-            Punctuation => 58..65
-            Slash => 47
-            ";
-
-    static TRANSITIONS: &'static str = 
-            "
-            Start => Alpha => Alpha
-            Start => Number => Number
-            Start => Whitespace => Whitespace
-            Start => Punctuation => Punctuation
-            Start => Slash => Slash
-            Slash => Slash => Slash
-            Slash => Whitespace => Whitespace
-            Slash => Alpha => Pos
-            Alpha => Alpha | Number => Alpha
-            Pos => Alpha => Pos
-            Number => Number => Number
-            Number => Alpha => Alpha
-            Whitespace => Whitespace => Whitespace
-            Punctuation => Punctuation => Punctuation
-            ";
 
     #[test]
     fn test_get_files() {
-        let tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+        let tokenizer = Tokenizer::standard("brown").unwrap();
 
         let brown_corpus = Corpus::new("/brown/", tokenizer);
 
@@ -136,11 +116,11 @@ mod tests {
 
     #[test]
     fn test_get_words() {
-        let tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+        let tokenizer = Tokenizer::standard("brown").unwrap();
 
         let mut brown_corpus = Corpus::new("/brown/", tokenizer);
 
-        let words = brown_corpus.words(0);
+        let words = brown_corpus.words(0).unwrap();
         let num_words = words.len();
 
         println!("{:?}", &words.into_iter().map(|w_v| String::from_utf8(w_v).unwrap()).collect::<Vec<String>>());
@@ -148,4 +128,27 @@ mod tests {
 
         assert!(num_words == 2088);
     }
+
+    #[test]
+    fn read_errors() {
+        let tokenizer = Tokenizer::standard("brown").unwrap();
+        let mut corpus = Corpus::new("./test_assets/", tokenizer);
+        let dir = corpus.get_scanners().iter().position(|s| s.get_file().ends_with("brown")).unwrap();
+
+        // a directory opens, but reading it fails
+        assert!(corpus.words(dir).is_err());
+        assert!(corpus.allwords().is_err());
+    }
+
+    #[test]
+    fn words_ref_unmapped_input() {
+        let tokenizer = Tokenizer::standard("brown").unwrap().unmapped(Unmapped::Error);
+        let corpus = Corpus::new("./test_assets/", tokenizer);
+
+        let words = corpus.words_ref(b"the jury said").unwrap();
+        assert_eq!(words, vec![Cow::from(&b"the"[..]), Cow::from(&b"jury"[..]), Cow::from(&b"said"[..])]);
+
+        let err = corpus.words_ref(b"caf\xc3\xa9").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
 }
\ No newline at end of file
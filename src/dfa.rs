@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use lexicon::*;
+use spec;
+use spec::{Spanned, SpecError};
+use tokenizer::*;
+
+// Token rules as regular expressions over bytes, compiled into one DFA
+// that finds the longest match at each position; when rules match the
+// same length, the one defined first wins. The syntax is:
+//
+//   abc           literal bytes; other characters match their UTF-8 bytes,
+//                 and a repetition after one repeats the whole character
+//   .             any byte but a newline
+//   [a-z_]        a class of bytes, [^...] for its complement; ASCII only
+//   \d \w \s      digits, word bytes and whitespace, \D \W \S the rest
+//   \n \r \t \xHH escaped bytes; `\` before punctuation makes it literal
+//   (a|b)         groups and alternation
+//   * + ? {n} {n,} {n,m}
+//                 repetition
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Empty,
+    Class(Vec<(u8, u8)>),
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat(Box<Node>, u32, Option<u32>),
+}
+
+impl Node {
+    fn matches_empty(&self) -> bool {
+        match *self {
+            Node::Empty => true,
+            Node::Class(_) => false,
+            Node::Concat(ref nodes) => nodes.iter().all(Node::matches_empty),
+            Node::Alt(ref nodes) => nodes.iter().any(Node::matches_empty),
+            Node::Repeat(ref node, min, _) => min == 0 || node.matches_empty()
+        }
+    }
+}
+
+const MAX_REPEAT: u32 = 255;
+
+const DIGIT: &[(u8, u8)] = &[(b'0', b'9')];
+const WORD: &[(u8, u8)] = &[(b'0', b'9'), (b'A', b'Z'), (b'_', b'_'), (b'a', b'z')];
+const SPACE: &[(u8, u8)] = &[(9, 13), (b' ', b' ')];
+
+fn complement(ranges: &[(u8, u8)]) -> Vec<(u8, u8)> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort();
+    let mut out = Vec::new();
+    let mut next = 0u16;
+    for (lo, hi) in sorted {
+        if (lo as u16) > next {
+            out.push((next as u8, lo - 1));
+        }
+        next = next.max(hi as u16 + 1);
+    }
+    if next <= 255 {
+        out.push((next as u8, 255));
+    }
+    out
+}
+
+struct Parser<'a> {
+    src: Spanned<'a>,
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn parse(src: Spanned<'a>) -> Result<Node, SpecError> {
+        let mut parser = Parser { src, bytes: src.text.as_bytes(), at: 0 };
+        let node = parser.alt()?;
+        if parser.at < parser.bytes.len() {
+            return Err(parser.error(parser.at, "unmatched `)`"));
+        }
+        Ok(node)
+    }
+    fn error(&self, at: usize, message: &str) -> SpecError {
+        self.src.sub(&self.src.text[at..]).error(message)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.at).cloned()
+    }
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek();
+        self.at += 1;
+        b
+    }
+    fn alt(&mut self) -> Result<Node, SpecError> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some(b'|') {
+            self.at += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Node::Alt(branches) })
+    }
+    fn concat(&mut self) -> Result<Node, SpecError> {
+        let mut items = Vec::new();
+        while let Some(b) = self.peek() {
+            if b == b'|' || b == b')' {
+                break;
+            }
+            let atom = self.atom()?;
+            items.push(self.repeat(atom)?);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items)
+        })
+    }
+    fn atom(&mut self) -> Result<Node, SpecError> {
+        let start = self.at;
+        match self.next().unwrap() {
+            b'(' => {
+                let node = self.alt()?;
+                if self.next() != Some(b')') {
+                    return Err(self.error(start, "unclosed `(`"));
+                }
+                Ok(node)
+            },
+            b'[' => self.class(start),
+            b'.' => Ok(Node::Class(complement(&[(b'\n', b'\n')]))),
+            b'\\' => Ok(Node::Class(self.escape(start)?)),
+            b'*' | b'+' | b'?' | b'{' => Err(self.error(start, "nothing to repeat")),
+            b if b >= 0x80 => {
+                let len = self.src.text.get(start..).and_then(|t| t.chars().next()).map_or(1, char::len_utf8);
+                self.at = start + len;
+                Ok(Node::Concat(self.bytes[start..self.at].iter().map(|&b| Node::Class(vec![(b, b)])).collect()))
+            },
+            b => Ok(Node::Class(vec![(b, b)]))
+        }
+    }
+    fn repeat(&mut self, mut node: Node) -> Result<Node, SpecError> {
+        loop {
+            let start = self.at;
+            let (min, max) = match self.peek() {
+                Some(b'*') => (0, None),
+                Some(b'+') => (1, None),
+                Some(b'?') => (0, Some(1)),
+                Some(b'{') => {
+                    self.at += 1;
+                    let min = self.count(start)?;
+                    let max = if self.peek() == Some(b',') {
+                        self.at += 1;
+                        if self.peek() == Some(b'}') { None } else { Some(self.count(start)?) }
+                    } else {
+                        Some(min)
+                    };
+                    if self.peek() != Some(b'}') {
+                        return Err(self.error(start, "expected `}` after the repetition count"));
+                    }
+                    if max.is_some_and(|max| max < min) {
+                        return Err(self.error(start, "repetition range goes backwards"));
+                    }
+                    (min, max)
+                },
+                _ => return Ok(node)
+            };
+            self.at += 1;
+            node = Node::Repeat(Box::new(node), min, max);
+        }
+    }
+    fn count(&mut self, start: usize) -> Result<u32, SpecError> {
+        let digits = self.bytes[self.at..].iter().take_while(|b| b.is_ascii_digit()).count();
+        let text = &self.src.text[self.at..self.at + digits];
+        self.at += digits;
+        match text.parse::<u32>() {
+            Ok(n) if n <= MAX_REPEAT => Ok(n),
+            Ok(_) => Err(self.error(start, &format!("repetition count above {}", MAX_REPEAT))),
+            Err(_) => Err(self.error(start, "expected a repetition count"))
+        }
+    }
+    // After the `\` at `start`.
+    fn escape(&mut self, start: usize) -> Result<Vec<(u8, u8)>, SpecError> {
+        let b = match self.next() {
+            Some(b) => b,
+            None => return Err(self.error(start, "expected a character after `\\`"))
+        };
+        Ok(match b {
+            b'd' => DIGIT.to_vec(),
+            b'w' => WORD.to_vec(),
+            b's' => SPACE.to_vec(),
+            b'D' => complement(DIGIT),
+            b'W' => complement(WORD),
+            b'S' => complement(SPACE),
+            b'n' => vec![(b'\n', b'\n')],
+            b'r' => vec![(b'\r', b'\r')],
+            b't' => vec![(b'\t', b'\t')],
+            b'x' => {
+                let hex = self.src.text.get(self.at..self.at + 2).and_then(|h| u8::from_str_radix(h, 16).ok());
+                match hex {
+                    Some(v) => {
+                        self.at += 2;
+                        vec![(v, v)]
+                    },
+                    None => return Err(self.error(start, "expected two hex digits after `\\x`"))
+                }
+            },
+            b if b.is_ascii_punctuation() || b == b' ' => vec![(b, b)],
+            _ => return Err(self.error(start, &format!("unknown escape `\\{}`", b as char)))
+        })
+    }
+    // After the `[` at `start`.
+    fn class(&mut self, start: usize) -> Result<Node, SpecError> {
+        let negate = self.peek() == Some(b'^');
+        if negate {
+            self.at += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let at = self.at;
+            let lo = match self.next() {
+                None => return Err(self.error(start, "unclosed `[`")),
+                Some(b']') if !first => break,
+                Some(b'\\') => {
+                    let escaped = self.escape(at)?;
+                    if escaped.len() != 1 || escaped[0].0 != escaped[0].1 {
+                        ranges.extend(escaped);
+                        first = false;
+                        continue;
+                    }
+                    escaped[0].0
+                },
+                Some(b) if b.is_ascii() => b,
+                Some(_) => return Err(self.error(at, "classes can only hold ASCII characters"))
+            };
+            first = false;
+            let is_range = self.peek() == Some(b'-') && self.bytes.get(self.at + 1).is_some_and(|&b| b != b']');
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+            self.at += 1;
+            let hi_at = self.at;
+            let hi = match self.next() {
+                Some(b'\\') => {
+                    let escaped = self.escape(hi_at)?;
+                    if escaped.len() != 1 || escaped[0].0 != escaped[0].1 {
+                        return Err(self.error(hi_at, "expected a single character to end the range"));
+                    }
+                    escaped[0].0
+                },
+                Some(b) if b.is_ascii() => b,
+                _ => return Err(self.error(hi_at, "classes can only hold ASCII characters"))
+            };
+            if hi < lo {
+                return Err(self.error(at, "class range goes backwards"));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Node::Class(if negate { complement(&ranges) } else { ranges }))
+    }
+}
+
+#[derive(Default)]
+struct NfaState {
+    eps: Vec<usize>,
+    ranges: Vec<(u8, u8, usize)>,
+    // index of the rule this state completes
+    accept: Option<usize>,
+}
+
+struct Nfa {
+    states: Vec<NfaState>,
+}
+
+impl Nfa {
+    fn add(&mut self) -> usize {
+        self.states.push(NfaState::default());
+        self.states.len() - 1
+    }
+    // Adds the states for `node` after `from`, returning where they end.
+    fn build(&mut self, node: &Node, from: usize) -> usize {
+        match *node {
+            Node::Empty => from,
+            Node::Class(ref ranges) => {
+                let to = self.add();
+                for &(lo, hi) in ranges {
+                    self.states[from].ranges.push((lo, hi, to));
+                }
+                to
+            },
+            Node::Concat(ref nodes) => nodes.iter().fold(from, |at, n| self.build(n, at)),
+            Node::Alt(ref nodes) => {
+                let to = self.add();
+                for n in nodes {
+                    let start = self.add();
+                    self.states[from].eps.push(start);
+                    let end = self.build(n, start);
+                    self.states[end].eps.push(to);
+                }
+                to
+            },
+            Node::Repeat(ref n, min, max) => {
+                let mut at = from;
+                for _ in 0..min {
+                    at = self.build(n, at);
+                }
+                match max {
+                    None => {
+                        let start = self.add();
+                        let to = self.add();
+                        self.states[at].eps.push(start);
+                        let end = self.build(n, start);
+                        self.states[end].eps.push(start);
+                        self.states[start].eps.push(to);
+                        to
+                    },
+                    Some(max) => {
+                        for _ in min..max {
+                            let to = self.add();
+                            self.states[at].eps.push(to);
+                            let end = self.build(n, at);
+                            self.states[end].eps.push(to);
+                            at = to;
+                        }
+                        at
+                    }
+                }
+            }
+        }
+    }
+    // The states reachable from `set` without reading a byte, sorted.
+    fn closure(&self, mut set: Vec<usize>) -> Vec<usize> {
+        let mut seen = vec![false; self.states.len()];
+        let mut todo = set.clone();
+        for &s in &set {
+            seen[s] = true;
+        }
+        while let Some(s) = todo.pop() {
+            for &e in &self.states[s].eps {
+                if !seen[e] {
+                    seen[e] = true;
+                    set.push(e);
+                    todo.push(e);
+                }
+            }
+        }
+        set.sort();
+        set
+    }
+}
+
+// A DFA state from subset construction, before minimizing.
+struct Subset {
+    next: [Option<usize>; 256],
+    accept: Option<TokenType>,
+}
+
+const DEAD: u32 = u32::MAX;
+
+// The minimized DFA as dense tables. Bytes that every state treats alike
+// share a class, so each state has one entry per class rather than per
+// byte.
+pub struct Dfa {
+    byte_classes: [u16; 256],
+    classes: usize,
+    // `classes` entries per state
+    next: Vec<u32>,
+    accept: Vec<Option<TokenType>>,
+}
+
+impl Dfa {
+    // Reads rules in the `Name = regex` format, interning their names in
+    // the tokenizer's symbol table. Rules that match the empty string are
+    // an error, since they would never consume anything.
+    pub fn compile(rules: &str, symbols: &mut Lexicon) -> Result<Self, SpecError> {
+        let mut nfa = Nfa { states: Vec::new() };
+        let start = nfa.add();
+        let mut types = Vec::new();
+        for (i, def) in spec::rule_defs(rules)?.into_iter().enumerate() {
+            let node = Parser::parse(def.values)?;
+            if node.matches_empty() {
+                return Err(def.values.error("rule matches the empty string"));
+            }
+            let rule_start = nfa.add();
+            nfa.states[start].eps.push(rule_start);
+            let end = nfa.build(&node, rule_start);
+            nfa.states[end].accept = Some(i);
+            types.push(TokenType(symbols.intern(def.name.text.as_bytes()).0));
+        }
+        if types.is_empty() {
+            return Err(SpecError::new(1, 1, "expected at least one rule"));
+        }
+
+        let mut sets = vec![nfa.closure(vec![start])];
+        let mut ids: HashMap<Vec<usize>, usize> = HashMap::new();
+        ids.insert(sets[0].clone(), 0);
+        let mut states = Vec::new();
+        while states.len() < sets.len() {
+            let set = sets[states.len()].clone();
+            let mut next = [None; 256];
+            for b in 0..=255u8 {
+                let targets: Vec<usize> = set.iter()
+                    .flat_map(|&s| nfa.states[s].ranges.iter())
+                    .filter(|r| r.0 <= b && b <= r.1)
+                    .map(|r| r.2)
+                    .collect();
+                if targets.is_empty() {
+                    continue;
+                }
+                let target = nfa.closure(targets);
+                let id = match ids.get(&target) {
+                    Some(&id) => id,
+                    None => {
+                        ids.insert(target.clone(), sets.len());
+                        sets.push(target);
+                        sets.len() - 1
+                    }
+                };
+                next[b as usize] = Some(id);
+            }
+            let accept = set.iter().filter_map(|&s| nfa.states[s].accept).min().map(|i| types[i].clone());
+            states.push(Subset { next, accept });
+        }
+        Ok(Dfa::minimize(&states))
+    }
+    // Merges states no input can tell apart, by splitting them by what
+    // they accept and then by where each byte takes them until no split
+    // changes anything. Blocks are numbered in state order, so the start
+    // state stays 0.
+    fn minimize(states: &[Subset]) -> Self {
+        let mut block = number(states.iter().map(|s| s.accept.as_ref().map(|t| t.0)));
+        loop {
+            let refined = number(states.iter().enumerate().map(|(i, s)| {
+                (block[i], s.next.iter().map(|n| n.map(|n| block[n])).collect::<Vec<_>>())
+            }));
+            let done = refined.iter().max() == block.iter().max();
+            block = refined;
+            if done {
+                break;
+            }
+        }
+        let count = block.iter().max().unwrap() + 1;
+        let mut first = vec![None; count];
+        for (i, &b) in block.iter().enumerate() {
+            first[b].get_or_insert(i);
+        }
+        let reps: Vec<&Subset> = first.iter().map(|&i| &states[i.unwrap()]).collect();
+
+        let column = |b: usize| reps.iter().map(|s| s.next[b].map(|n| block[n])).collect::<Vec<_>>();
+        let byte_class = number((0..256).map(column));
+        let classes = byte_class.iter().max().unwrap() + 1;
+        let mut byte_classes = [0u16; 256];
+        let mut next = vec![DEAD; count * classes];
+        for (b, &class) in byte_class.iter().enumerate() {
+            byte_classes[b] = class as u16;
+            for (i, s) in reps.iter().enumerate() {
+                if let Some(n) = s.next[b] {
+                    next[i * classes + class] = block[n] as u32;
+                }
+            }
+        }
+        Dfa {
+            byte_classes,
+            classes,
+            next,
+            accept: reps.iter().map(|s| s.accept.clone()).collect()
+        }
+    }
+    pub fn start(&self) -> usize {
+        0
+    }
+    pub fn next(&self, state: usize, b: u8) -> Option<usize> {
+        match self.next[state * self.classes + self.byte_classes[b as usize] as usize] {
+            DEAD => None,
+            n => Some(n as usize)
+        }
+    }
+    // The token type of the rule a match ending in this state is for.
+    pub fn accept(&self, state: usize) -> Option<&TokenType> {
+        self.accept[state].as_ref()
+    }
+    pub fn len(&self) -> usize {
+        self.accept.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.accept.is_empty()
+    }
+    // The number of byte classes.
+    pub fn classes(&self) -> usize {
+        self.classes
+    }
+    pub fn byte_class(&self, b: u8) -> usize {
+        self.byte_classes[b as usize] as usize
+    }
+}
+
+// Numbers the distinct keys in order of first appearance.
+fn number<K: Eq + Hash, I: Iterator<Item = K>>(keys: I) -> Vec<usize> {
+    let mut ids = HashMap::new();
+    keys.map(|k| {
+        let n = ids.len();
+        *ids.entry(k).or_insert(n)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn longest(dfa: &Dfa, input: &[u8]) -> Option<(usize, TokenType)> {
+        let mut state = dfa.start();
+        let mut found = None;
+        for (i, &b) in input.iter().enumerate() {
+            state = match dfa.next(state, b) {
+                Some(next) => next,
+                None => break
+            };
+            if let Some(t) = dfa.accept(state) {
+                found = Some((i + 1, t.clone()));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn longest_match_then_first_rule() {
+        let mut symbols = Tokenizer::symbol_table();
+        let dfa = Dfa::compile("
+            If = if
+            Word = [A-Za-z]+('[a-z]+)?
+            Decimal = \\d+(\\.\\d+)?
+            Hex = 0x[0-9a-fA-F]{1,4}
+            ", &mut symbols).unwrap();
+        let t = |name: &str| TokenType(symbols.get(name.as_bytes()).unwrap().0);
+
+        assert_eq!(longest(&dfa, b"if "), Some((2, t("If"))));
+        assert_eq!(longest(&dfa, b"iffy"), Some((4, t("Word"))));
+        assert_eq!(longest(&dfa, b"don't!"), Some((5, t("Word"))));
+        assert_eq!(longest(&dfa, b"3.14."), Some((4, t("Decimal"))));
+        assert_eq!(longest(&dfa, b"3."), Some((1, t("Decimal"))));
+        assert_eq!(longest(&dfa, b"0xbeef0"), Some((6, t("Hex"))));
+        assert_eq!(longest(&dfa, b"-1"), None);
+    }
+
+    #[test]
+    fn multibyte_characters() {
+        let mut symbols = Tokenizer::symbol_table();
+        let dfa = Dfa::compile("Word = é+\nName = na(ï|i)ve", &mut symbols).unwrap();
+        let t = |name: &str| TokenType(symbols.get(name.as_bytes()).unwrap().0);
+
+        assert_eq!(longest(&dfa, "ééé".as_bytes()), Some((6, t("Word"))));
+        assert_eq!(longest(&dfa, "naïve".as_bytes()), Some((6, t("Name"))));
+        // the second byte of é alone does not repeat
+        assert_eq!(longest(&dfa, b"\xc3\xa9\xa9"), Some((2, t("Word"))));
+
+        let mut tokenizer = Tokenizer::regex("Word = é+").unwrap();
+
+        assert_eq!(tokenizer.tokenize("ééé".as_bytes()).len(), 1);
+    }
+
+    #[test]
+    fn minimized_tables() {
+        let mut symbols = Tokenizer::symbol_table();
+        // the states after `a` and after `c` merge, and so do `a` and `c`
+        let dfa = Dfa::compile("Word = ab|cb", &mut symbols).unwrap();
+
+        assert_eq!(dfa.len(), 3);
+        assert_eq!(dfa.classes(), 3);
+
+        let dfa = Dfa::compile("Word = [a-z]+\nNumber = [0-9]+", &mut symbols).unwrap();
+
+        assert_eq!(dfa.len(), 3);
+        assert_eq!(dfa.classes(), 3);
+        assert_eq!(dfa.next(dfa.start(), b'q'), dfa.next(dfa.start(), b'e'));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let mut symbols = Tokenizer::symbol_table();
+        let error = |rules: &str| {
+            let e = Dfa::compile(rules, &mut Tokenizer::symbol_table()).err().unwrap();
+            ((e.line, e.column), e.message)
+        };
+
+        assert!(Dfa::compile("Url = https?://[^ ]+\nEmail = [\\w.]+@\\w+(\\.\\w+)+", &mut symbols).is_ok());
+        assert_eq!(error("Word = [a-z"), ((1, 8), String::from("unclosed `[`")));
+        assert_eq!(error("Word = (ab"), ((1, 8), String::from("unclosed `(`")));
+        assert_eq!(error("\nWord = ab)"), ((2, 10), String::from("unmatched `)`")));
+        assert_eq!(error("Word = +a"), ((1, 8), String::from("nothing to repeat")));
+        assert_eq!(error("Word = a{3,2}"), ((1, 9), String::from("repetition range goes backwards")));
+        assert_eq!(error("Word = [z-a]"), ((1, 9), String::from("class range goes backwards")));
+        assert_eq!(error("Word = a*"), ((1, 8), String::from("rule matches the empty string")));
+        assert_eq!(error("Word [a-z]+"), ((1, 12), String::from("expected `=` after the rule name")));
+    }
+}
@@ -0,0 +1,161 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+
+// A document made of named fields, each indexed separately as well as
+// together, e.g. the category, title and body of a Brown file.
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub tokens: Vec<Vec<u8>>,
+}
+
+// The original text of a field, kept so results can be shown without
+// going back to the corpus. Large sources can be stored as a byte range of
+// the file instead of a copy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stored {
+    Text(Vec<u8>),
+    Offsets { path: PathBuf, start: u64, end: u64 },
+}
+
+impl Stored {
+    pub fn load(&self) -> io::Result<Vec<u8>> {
+        match *self {
+            Stored::Text(ref text) => Ok(text.clone()),
+            Stored::Offsets { ref path, start, end } => {
+                let mut f = File::open(path)?;
+                f.seek(SeekFrom::Start(start))?;
+                let mut text = Vec::with_capacity(end.saturating_sub(start) as usize);
+                f.take(end.saturating_sub(start)).read_to_end(&mut text)?;
+                Ok(text)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldNameError {
+    pub name: String,
+}
+
+impl fmt::Display for FieldNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "field name {:?} contains a NUL byte", self.name)
+    }
+}
+
+impl Error for FieldNameError {}
+
+// stored values by field name
+pub type StoredFields = Vec<(String, Stored)>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Document {
+    pub fields: Vec<Field>,
+    pub stored: StoredFields,
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document {
+            fields: Vec::new(),
+            stored: Vec::new()
+        }
+    }
+    pub fn field(mut self, name: &str, tokens: Vec<Vec<u8>>) -> Result<Self, FieldNameError> {
+        self.add_field(name, tokens)?;
+        Ok(self)
+    }
+    // Field names cannot contain NUL, which separates them from the term
+    // in `field_term` keys.
+    pub fn add_field(&mut self, name: &str, tokens: Vec<Vec<u8>>) -> Result<(), FieldNameError> {
+        if name.as_bytes().contains(&0) {
+            return Err(FieldNameError { name: String::from(name) });
+        }
+        self.fields.push(Field {
+            name: String::from(name),
+            tokens
+        });
+        Ok(())
+    }
+    pub fn store(mut self, name: &str, value: Stored) -> Self {
+        self.add_stored(name, value);
+        self
+    }
+    // Storing a name twice replaces the earlier value.
+    pub fn add_stored(&mut self, name: &str, value: Stored) {
+        match self.stored.iter_mut().find(|s| s.0 == name) {
+            Some(s) => s.1 = value,
+            None => self.stored.push((String::from(name), value))
+        }
+    }
+    pub fn get_stored(&self, name: &str) -> Option<&Stored> {
+        self.stored.iter().find(|s| s.0 == name).map(|s| &s.1)
+    }
+    pub fn get_field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+    pub fn len(&self) -> usize {
+        self.fields.iter().map(|f| f.tokens.len()).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Lexicon key for a term in one field, the field name and the term
+// separated by NUL. Field names never contain NUL, and the index leaves out
+// unqualified terms that do, so a key with a NUL is always one of these.
+pub fn field_term(field: &str, term: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(field.len() + 1 + term.len());
+    key.extend_from_slice(field.as_bytes());
+    key.push(0);
+    key.extend_from_slice(term);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn build_document() {
+        let doc = Document::new()
+            .field("title", vec![b"grand".to_vec(), b"jury".to_vec()]).unwrap()
+            .field("body", vec![b"said".to_vec()]).unwrap();
+
+        assert_eq!(doc.len(), 3);
+        assert_eq!(doc.get_field("body").unwrap().tokens, vec![b"said".to_vec()]);
+        assert!(doc.get_field("category").is_none());
+        assert_eq!(field_term("title", b"jury"), b"title\0jury".to_vec());
+
+        let mut doc = Document::new();
+        let e = doc.add_field("ti\0tle", vec![b"jury".to_vec()]).unwrap_err();
+
+        assert_eq!(e.to_string(), "field name \"ti\\0tle\" contains a NUL byte");
+        assert!(doc.fields.is_empty());
+    }
+
+    #[test]
+    fn stored_fields() {
+        let path = ::std::env::temp_dir().join(format!("graffiti-stored-{}.txt", ::std::process::id()));
+        ::std::fs::write(&path, b"The Fulton County Grand Jury said").unwrap();
+
+        let doc = Document::new()
+            .store("title", Stored::Text(b"Jury".to_vec()))
+            .store("body", Stored::Offsets { path: path.clone(), start: 4, end: 17 });
+
+        assert_eq!(doc.get_stored("title").unwrap().load().unwrap(), b"Jury".to_vec());
+        assert_eq!(doc.get_stored("body").unwrap().load().unwrap(), b"Fulton County".to_vec());
+        assert!(doc.get_stored("category").is_none());
+        assert!(doc.is_empty());
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
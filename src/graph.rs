@@ -0,0 +1,229 @@
+use std::fmt::Write;
+
+use tokenizer::*;
+
+// Renders a compiled tokenizer for review, with its state and token type
+// names: as a Graphviz graph (`dot -Tsvg`), or as text tables of which
+// bytes get which type and where each state goes on each type. Bytes no
+// definition covers are listed as unmapped; the tokenizer's `Unmapped`
+// policy decides what becomes of them. Regex rule tokenizers are shown as
+// their DFA, with numbered states and transitions on byte classes.
+
+// A set of bytes as runs, like `A-Z a-z \x27`.
+fn byte_set(bytes: &[u8]) -> String {
+    let show = |b: u8| match b {
+        b'\t' => String::from("\\t"),
+        b'\n' => String::from("\\n"),
+        b'\r' => String::from("\\r"),
+        b'-' | b'\\' => format!("\\x{:02x}", b),
+        _ if b.is_ascii_graphic() => (b as char).to_string(),
+        _ => format!("\\x{:02x}", b)
+    };
+    let mut runs: Vec<(u8, u8)> = Vec::new();
+    for &b in bytes {
+        match runs.last_mut() {
+            Some(run) if run.1 as u16 + 1 == b as u16 => run.1 = b,
+            _ => runs.push((b, b))
+        }
+    }
+    runs.iter()
+        .map(|&(lo, hi)| if lo == hi { show(lo) } else { format!("{}-{}", show(lo), show(hi)) })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Each type's bytes, then the unmapped ones.
+fn byte_map(tokenizer: &Tokenizer) -> Vec<(String, String)> {
+    let top = if tokenizer.is_unicode() { 128 } else { 256 };
+    let mut rows: Vec<(String, String)> = tokenizer.token_types().iter()
+        .map(|t| {
+            let bytes: Vec<u8> = (0..top).map(|b| b as u8).filter(|&b| tokenizer.byte_type(b) == Some(t)).collect();
+            (String::from(tokenizer.type_name(t)), byte_set(&bytes))
+        })
+        .filter(|r| !r.1.is_empty())
+        .collect();
+    let unmapped: Vec<u8> = (0..top).map(|b| b as u8).filter(|&b| tokenizer.byte_type(b).is_none()).collect();
+    if !unmapped.is_empty() {
+        rows.push((String::from("(unmapped)"), byte_set(&unmapped)));
+    }
+    rows
+}
+
+// The DFA's byte classes, as their bytes.
+fn rule_classes(tokenizer: &Tokenizer) -> Vec<String> {
+    let dfa = tokenizer.rules().unwrap();
+    (0..dfa.classes())
+        .map(|c| byte_set(&(0..=255u8).filter(|&b| dfa.byte_class(b) == c).collect::<Vec<u8>>()))
+        .collect()
+}
+
+fn rule_state(tokenizer: &Tokenizer, state: usize) -> String {
+    match tokenizer.rules().unwrap().accept(state) {
+        Some(t) => format!("{} {}", state, tokenizer.type_name(t)),
+        None => state.to_string()
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn to_dot(tokenizer: &Tokenizer) -> String {
+    let mut out = String::from("digraph tokenizer {\n    rankdir=LR;\n    node [shape=ellipse];\n");
+    if let Some(dfa) = tokenizer.rules() {
+        let classes = rule_classes(tokenizer);
+        for state in 0..dfa.len() {
+            let shape = if dfa.accept(state).is_some() { "doublecircle" } else { "circle" };
+            writeln!(out, "    s{} [label={}, shape={}];", state, quote(&rule_state(tokenizer, state)), shape).unwrap();
+        }
+        for state in 0..dfa.len() {
+            // one edge per target, labelled with all the classes leading there
+            let mut edges: Vec<(usize, Vec<&str>)> = Vec::new();
+            for (c, bytes) in classes.iter().enumerate() {
+                let b = (0..=255u8).find(|&b| dfa.byte_class(b) == c).unwrap();
+                if let Some(to) = dfa.next(state, b) {
+                    match edges.iter_mut().find(|e| e.0 == to) {
+                        Some(e) => e.1.push(bytes),
+                        None => edges.push((to, vec![bytes]))
+                    }
+                }
+            }
+            for (to, labels) in edges {
+                writeln!(out, "    s{} -> s{} [label={}];", state, to, quote(&labels.join(" "))).unwrap();
+            }
+        }
+        out.push_str("}\n");
+        return out;
+    }
+    writeln!(out, "    {} [shape=doublecircle];", quote("Start")).unwrap();
+    for s in tokenizer.states() {
+        let mut edges: Vec<(&State, Vec<&str>)> = Vec::new();
+        for t in tokenizer.token_types() {
+            if let Some(to) = tokenizer.next_state(s, t) {
+                match edges.iter_mut().find(|e| e.0 == to) {
+                    Some(e) => e.1.push(tokenizer.type_name(t)),
+                    None => edges.push((to, vec![tokenizer.type_name(t)]))
+                }
+            }
+        }
+        for (to, labels) in edges {
+            writeln!(out, "    {} -> {} [label={}];",
+                quote(tokenizer.state_name(s)), quote(tokenizer.state_name(to)), quote(&labels.join(" | "))).unwrap();
+        }
+    }
+    let mut bytes = String::new();
+    for (name, set) in byte_map(tokenizer) {
+        write!(bytes, "{}: {}\\l", name, set.replace('\\', "\\\\").replace('"', "\\\"")).unwrap();
+    }
+    writeln!(out, "    bytes [shape=note, label=\"{}\"];", bytes).unwrap();
+    out.push_str("}\n");
+    out
+}
+
+// Rows of cells as left-aligned columns.
+fn columns(rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap())
+        .collect();
+    let mut out = String::new();
+    for row in rows {
+        let line: Vec<String> = row.iter().zip(&widths).map(|(cell, &w)| format!("{:<w$}", cell, w = w)).collect();
+        out.push_str(line.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+pub fn to_table(tokenizer: &Tokenizer) -> String {
+    let mut out = String::new();
+    if let Some(dfa) = tokenizer.rules() {
+        let classes = rule_classes(tokenizer);
+        out.push_str("Byte classes\n");
+        let rows: Vec<Vec<String>> = classes.iter().enumerate().map(|(c, set)| vec![format!("  {}", c), set.clone()]).collect();
+        out.push_str(&columns(&rows));
+        out.push_str("\nTransitions\n");
+        let mut rows = vec![Some(String::from("  State")).into_iter().chain((0..classes.len()).map(|c| c.to_string())).collect::<Vec<String>>()];
+        for state in 0..dfa.len() {
+            let mut row = vec![format!("  {}", rule_state(tokenizer, state))];
+            for c in 0..classes.len() {
+                let b = (0..=255u8).find(|&b| dfa.byte_class(b) == c).unwrap();
+                row.push(dfa.next(state, b).map_or(String::from("-"), |to| to.to_string()));
+            }
+            rows.push(row);
+        }
+        out.push_str(&columns(&rows));
+        return out;
+    }
+    out.push_str(if tokenizer.is_unicode() { "ASCII bytes\n" } else { "Bytes\n" });
+    let rows: Vec<Vec<String>> = byte_map(tokenizer).into_iter().map(|(name, set)| vec![format!("  {}", name), set]).collect();
+    if !rows.is_empty() {
+        out.push_str(&columns(&rows));
+    }
+    out.push_str("\nTransitions\n");
+    let types = tokenizer.token_types();
+    let mut rows = vec![Some(String::from("  State")).into_iter().chain(types.iter().map(|t| String::from(tokenizer.type_name(t)))).collect::<Vec<String>>()];
+    for s in tokenizer.states() {
+        let mut row = vec![format!("  {}", tokenizer.state_name(s))];
+        for t in types {
+            row.push(tokenizer.next_state(s, t).map_or(String::from("-"), |to| String::from(tokenizer.state_name(to))));
+        }
+        rows.push(row);
+    }
+    out.push_str(&columns(&rows));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    static TOKENS: &str = "
+        Alpha => 65..91
+        Alpha => 97..123
+        Whitespace => 9,10,13,32
+        Punctuation => 33..47
+        ";
+
+    static TRANSITIONS: &str = "
+        Start => Alpha => Alpha
+        Start => Whitespace => Whitespace
+        Start => Punctuation => Punctuation
+        Alpha => Alpha => Alpha
+        Whitespace => Whitespace => Whitespace
+        Punctuation => Punctuation => Punctuation
+        ";
+
+    #[test]
+    fn table_shows_names_and_unmapped_bytes() {
+        let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+
+        let table = to_table(&tokenizer);
+
+        assert!(table.contains("  Alpha        A-Z a-z\n"));
+        assert!(table.contains("  Whitespace   \\t-\\n \\r \\x20\n"));
+        // `^` is not covered, which is why it gets dropped
+        assert!(table.lines().any(|l| l.starts_with("  (unmapped)") && l.contains(" [-` ")));
+        assert!(table.contains("  State        Alpha  Whitespace  Punctuation\n"));
+        assert!(table.contains("  Start        Alpha  Whitespace  Punctuation\n"));
+        assert!(table.contains("  Alpha        Alpha  -           -\n"));
+    }
+
+    #[test]
+    fn dot_graph() {
+        let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+
+        let dot = to_dot(&tokenizer);
+
+        assert!(dot.starts_with("digraph tokenizer {"));
+        assert!(dot.contains("    \"Start\" -> \"Alpha\" [label=\"Alpha\"];\n"));
+        assert!(dot.contains("Whitespace: \\\\t-\\\\n \\\\r \\\\x20\\l"));
+
+        let rules = Tokenizer::regex("Word = [a-z]+\nNumber = [0-9]+").unwrap();
+        let dot = to_dot(&rules);
+
+        assert!(dot.contains("    s2 [label=\"2 Word\", shape=doublecircle];\n"));
+        assert!(dot.contains("    s0 -> s2 [label=\"a-z\"];\n"));
+        assert!(to_table(&rules).contains("  1 Number  -  1  -\n"));
+    }
+}
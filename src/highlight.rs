@@ -0,0 +1,262 @@
+use std::collections::HashSet;
+
+use tokenizer::*;
+
+// Picks the passages of a text that best match a query and marks the query
+// terms in them. Works from the tokens `Tokenizer::tokenize` produced for
+// the text, whose byte offsets say where to cut passages and put markers.
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Passage {
+    pub start: usize,
+    pub end: usize,
+    // distinct query terms in the passage, then the total number of matches
+    pub score: (usize, usize),
+    // the passage with its matches marked
+    pub text: Vec<u8>,
+}
+
+pub struct Highlighter {
+    pre: Vec<u8>,
+    post: Vec<u8>,
+    separator: Vec<u8>,
+    fragment_len: usize,
+    max_passages: usize,
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Highlighter {
+            pre: b"<b>".to_vec(),
+            post: b"</b>".to_vec(),
+            separator: b" ... ".to_vec(),
+            fragment_len: 100,
+            max_passages: 3
+        }
+    }
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        Highlighter::default()
+    }
+    pub fn markers(mut self, pre: &str, post: &str) -> Self {
+        self.pre = pre.as_bytes().to_vec();
+        self.post = post.as_bytes().to_vec();
+        self
+    }
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.as_bytes().to_vec();
+        self
+    }
+    // Passages are at most this many bytes long, unless a single match is
+    // longer.
+    pub fn fragment_len(mut self, len: usize) -> Self {
+        self.fragment_len = len;
+        self
+    }
+    pub fn max_passages(mut self, n: usize) -> Self {
+        self.max_passages = n;
+        self
+    }
+    // The best non-overlapping passages in text order. Terms match tokens
+    // ignoring ASCII case.
+    pub fn passages(&self, text: &[u8], tokens: &[Token], terms: &[Vec<u8>]) -> Vec<Passage> {
+        let matches: Vec<(usize, usize)> = tokens.iter()
+            .enumerate()
+            .filter_map(|(i, tok)| terms.iter()
+                .position(|t| !t.is_empty() && t.eq_ignore_ascii_case(&tok.value))
+                .map(|t| (i, t)))
+            .collect();
+
+        // every match starts a candidate running over the matches after it
+        // that still fit in a fragment
+        let mut candidates: Vec<(usize, usize, (usize, usize))> = Vec::new();
+        for (a, &(first, _)) in matches.iter().enumerate() {
+            let start = tokens[first].start;
+            let mut end = tokens[first].end;
+            let mut distinct = HashSet::new();
+            let mut count = 0;
+            for &(m, t) in &matches[a..] {
+                if count > 0 && tokens[m].end - start > self.fragment_len {
+                    break;
+                }
+                end = tokens[m].end;
+                distinct.insert(t);
+                count += 1;
+            }
+            candidates.push((start, end, (distinct.len(), count)));
+        }
+        candidates.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+
+        let mut chosen: Vec<(usize, usize, (usize, usize))> = Vec::new();
+        for c in candidates {
+            if chosen.len() == self.max_passages {
+                break;
+            }
+            if chosen.iter().all(|p| c.1 <= p.0 || c.0 >= p.1) {
+                chosen.push(c);
+            }
+        }
+        chosen.sort_by_key(|c| c.0);
+
+        // each passage widens up to the next one's matches, and the next
+        // one only back to where this one ended up
+        let mut passages: Vec<Passage> = Vec::with_capacity(chosen.len());
+        for (i, &(start, end, score)) in chosen.iter().enumerate() {
+            let lo = passages.last().map_or(0, |p| p.end);
+            let hi = chosen.get(i + 1).map_or(text.len(), |c| c.0);
+            let (start, end) = self.widen(text, tokens, start, end, lo, hi);
+            passages.push(Passage {
+                start,
+                end,
+                score,
+                text: self.mark(text, tokens, &matches, start, end)
+            });
+        }
+        passages
+    }
+    // The best passages joined by the separator, or the start of the text
+    // if nothing matched.
+    pub fn highlight(&self, text: &[u8], tokens: &[Token], terms: &[Vec<u8>]) -> Vec<u8> {
+        let passages = self.passages(text, tokens, terms);
+        if passages.is_empty() {
+            let (start, end) = self.widen(text, tokens, 0, 0, 0, text.len());
+            return text[start..end].to_vec();
+        }
+        let mut out = Vec::new();
+        for (i, p) in passages.iter().enumerate() {
+            if i > 0 {
+                out.extend_from_slice(&self.separator);
+            }
+            out.extend_from_slice(&p.text);
+        }
+        out
+    }
+    // Spends what is left of the fragment length on context around
+    // [start, end), half before and half after, cutting only at token
+    // boundaries and staying within [lo, hi).
+    fn widen(&self, text: &[u8], tokens: &[Token], start: usize, end: usize, lo: usize, hi: usize) -> (usize, usize) {
+        let budget = self.fragment_len.saturating_sub(end - start);
+        let want_start = start.saturating_sub(budget / 2).max(lo);
+        let new_start = tokens.iter()
+            .map(|t| t.start)
+            .filter(|&s| s >= want_start && s <= start)
+            .min()
+            .unwrap_or(start);
+        let want_end = (end + budget - (start - new_start)).min(hi);
+        let new_end = tokens.iter()
+            .map(|t| t.end)
+            .filter(|&e| e <= want_end && e >= end)
+            .max()
+            .unwrap_or(end)
+            .min(text.len());
+        let mut new_start = new_start.min(new_end);
+        let mut new_end = new_end;
+        while new_start < start && text[new_start].is_ascii_whitespace() {
+            new_start += 1;
+        }
+        while new_end > end && text[new_end - 1].is_ascii_whitespace() {
+            new_end -= 1;
+        }
+        (new_start, new_end)
+    }
+    fn mark(&self, text: &[u8], tokens: &[Token], matches: &[(usize, usize)], start: usize, end: usize) -> Vec<u8> {
+        let mut out = Vec::with_capacity(end - start);
+        let mut at = start;
+        for &(m, _) in matches {
+            let tok = &tokens[m];
+            if tok.start < at || tok.end > end {
+                continue;
+            }
+            out.extend_from_slice(&text[at..tok.start]);
+            out.extend_from_slice(&self.pre);
+            out.extend_from_slice(&text[tok.start..tok.end]);
+            out.extend_from_slice(&self.post);
+            at = tok.end;
+        }
+        out.extend_from_slice(&text[at..end]);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    static TOKENS: &str = "
+        Alpha => 65..91
+        Alpha => 97..123
+        Whitespace => 9,10,13,32
+        Punctuation => 33..47
+        ";
+
+    static TRANSITIONS: &str = "
+        Start => Alpha => Alpha
+        Start => Whitespace => Whitespace
+        Start => Punctuation => Punctuation
+        Alpha => Alpha => Alpha
+        Whitespace => Whitespace => Whitespace
+        Punctuation => Punctuation => Punctuation
+        ";
+
+    fn words(s: &str) -> Vec<Vec<u8>> {
+        s.split_whitespace().map(|w| w.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn marks_terms_ignoring_case() {
+        let text = b"The Fulton County Grand Jury said Friday.";
+        let tokens = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().tokenize(text);
+
+        let out = Highlighter::new().highlight(text, &tokens, &words("grand jury"));
+
+        assert_eq!(String::from_utf8(out).unwrap(), "The Fulton County <b>Grand</b> <b>Jury</b> said Friday.");
+    }
+
+    #[test]
+    fn picks_best_passages() {
+        let text = b"A jury was picked. Nothing else happened for a long while here. \
+                     Then the grand jury said the grand jury had met. More filler text.";
+        let tokens = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().tokenize(text);
+        let hl = Highlighter::new().fragment_len(30).max_passages(1).markers("[", "]");
+
+        let passages = hl.passages(text, &tokens, &words("grand jury"));
+
+        assert_eq!(passages.len(), 1);
+        assert_eq!(passages[0].score, (2, 4));
+        assert!(passages[0].end - passages[0].start <= 30);
+        assert!(String::from_utf8_lossy(&passages[0].text).contains("[grand] [jury] said the [grand] [jury]"));
+
+        let two = Highlighter::new().fragment_len(30).max_passages(2).passages(text, &tokens, &words("grand jury"));
+
+        assert_eq!(two.len(), 2);
+        assert!(two[0].end <= two[1].start);
+        assert!(String::from_utf8_lossy(&two[0].text).starts_with("A <b>jury</b>"));
+    }
+
+    #[test]
+    fn nearby_passages_do_not_overlap() {
+        // too far apart for one fragment, but each widens into the gap
+        let text = b"grand one two three four jury";
+        let tokens = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().tokenize(text);
+        let hl = Highlighter::new().fragment_len(24).max_passages(2);
+
+        let passages = hl.passages(text, &tokens, &words("grand jury"));
+
+        assert_eq!(passages.len(), 2);
+        assert!(passages[0].end <= passages[1].start);
+        assert_eq!(hl.highlight(text, &tokens, &words("grand jury")), b"<b>grand</b> one two three four ... <b>jury</b>".to_vec());
+    }
+
+    #[test]
+    fn falls_back_to_leading_text() {
+        let text = b"Nothing to see in this text at all.";
+        let tokens = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().tokenize(text);
+
+        let out = Highlighter::new().fragment_len(12).highlight(text, &tokens, &words("jury"));
+
+        assert_eq!(out, b"Nothing to".to_vec());
+    }
+}
@@ -1,91 +1,634 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::Path;
+use lexicon::*;
+use scoring::*;
+use proximity::*;
+use postings::*;
+use query::*;
+use topk::*;
+use segment::*;
+use document::*;
 
+static NO_POSTINGS: Vec<u32> = Vec::new();
+static NO_POSITIONS: Vec<Vec<u32>> = Vec::new();
+
+// Positions skipped between consecutive fields of a document, so phrase and
+// proximity matches over the whole document do not run across fields.
+pub const POSITION_GAP: u32 = 100;
+
+type Positioned<'a> = Vec<(u32,&'a [u8])>;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FieldStats {
+    pub lens: HashMap<u32,u32>,
+    pub norms: HashMap<u32,f32>,
+    pub total_len: u64,
+}
+
+#[derive(Default)]
 pub struct InvertedIndex {
-    dictionary: HashMap<u32,Vec<u32>>,
-    tfs: HashMap<u32,Vec<u32>>,
-    idfs: HashMap<u32,u32>,
+    lexicon: Lexicon,
+    dictionary: HashMap<TermId,Vec<u32>>,
+    tfs: HashMap<TermId,Vec<u32>>,
+    positions: HashMap<TermId,Vec<Vec<u32>>>,
+    idfs: HashMap<TermId,u32>,
     tws: HashMap<u32,f32>,
+    doc_lens: HashMap<u32,u32>,
+    doc_terms: HashMap<u32,Vec<TermId>>,
+    // largest tf and shortest doc length seen in each postings list, kept as
+    // score upper bounds for `search`; removals leave them loose but valid
+    term_bounds: HashMap<TermId,(u32,u32)>,
+    fields: HashMap<String,FieldStats>,
+    stored: HashMap<u32,StoredFields>,
+    total_len: u64,
 }
 
 impl InvertedIndex {
     pub fn new() -> Self {
         InvertedIndex {
+            lexicon: Lexicon::new(),
             dictionary: HashMap::new(),
             tfs: HashMap::new(),
+            positions: HashMap::new(),
             idfs: HashMap::new(),
-            tws: HashMap::new()
+            tws: HashMap::new(),
+            doc_lens: HashMap::new(),
+            doc_terms: HashMap::new(),
+            term_bounds: HashMap::new(),
+            fields: HashMap::new(),
+            stored: HashMap::new(),
+            total_len: 0
         }
     }
-    pub fn get_hash_val(&self, token: &[u8]) -> u32 {
-        let mut hash_val: u32 = 0;
-        for i in 0..token.len() {
-            hash_val += i as u32 * token[i] as u32;
-        };
-        hash_val
+    pub fn get_term_id(&self, token: &[u8]) -> Option<TermId> {
+        self.lexicon.get(token)
     }
     pub fn get_docs(&self, token: &[u8]) -> &Vec<u32> {
-        let s: u32 = self.get_hash_val(token);
-        &self.dictionary[&s]
+        match self.get_term_id(token) {
+            Some(s) => &self.dictionary[&s],
+            None => &NO_POSTINGS
+        }
     }
     pub fn get_tfs(&self, token: &[u8]) -> &Vec<u32> {
-        let s: u32 = self.get_hash_val(token);
-        &self.tfs[&s]
+        match self.get_term_id(token) {
+            Some(s) => &self.tfs[&s],
+            None => &NO_POSTINGS
+        }
+    }
+    pub fn get_positions(&self, token: &[u8]) -> &Vec<Vec<u32>> {
+        match self.get_term_id(token) {
+            Some(s) => &self.positions[&s],
+            None => &NO_POSITIONS
+        }
     }
     pub fn get_idf(&self, token: &[u8]) -> u32 {
-        let s: u32 = self.get_hash_val(token);
-        self.idfs[&s]
-    }
-    pub fn add_doc(&mut self, tokens: &[Vec<u8>], doc: u32) {
-        let mut token_freqs = HashMap::new();
-        for token in tokens {
-            let t = token_freqs.entry(token).or_insert(0);
-            *t += 1;
-        }
-        for (token, freq) in token_freqs {
-            let s: u32 = self.get_hash_val(token);
-            self.dictionary.entry(s).or_insert(Vec::new()).push(doc);
-            self.tfs.entry(s).or_insert(Vec::new()).push(freq);
+        match self.get_term_id(token) {
+            Some(s) => self.idfs[&s],
+            None => 0
+        }
+    }
+    pub fn get_doc_len(&self, doc: u32) -> u32 {
+        self.doc_lens.get(&doc).cloned().unwrap_or(0)
+    }
+    pub fn num_docs(&self) -> u32 {
+        self.doc_lens.len() as u32
+    }
+    pub fn avg_doc_len(&self) -> f64 {
+        if self.doc_lens.is_empty() {
+            return 0.0;
+        }
+        self.total_len as f64 / self.doc_lens.len() as f64
+    }
+    pub fn collection_stats(&self) -> CollectionStats {
+        CollectionStats {
+            num_docs: self.num_docs(),
+            total_len: self.total_len,
+            avg_doc_len: self.avg_doc_len()
+        }
+    }
+    pub fn contains_doc(&self, doc: u32) -> bool {
+        self.doc_lens.contains_key(&doc)
+    }
+    // Postings are kept sorted by doc id. Adding a doc id that is already
+    // indexed replaces the old document, same as `update_doc`. Tokens
+    // containing NUL are left out of the postings, see `field_term`.
+    pub fn add_doc<T: AsRef<[u8]>>(&mut self, tokens: &[T], doc: u32) {
+        self.remove_doc(doc);
+        let positioned: Positioned = tokens.iter()
+            .enumerate()
+            .map(|(pos, token)| (pos as u32, token.as_ref()))
+            .collect();
+        self.index_tokens(doc, None, &positioned, tokens.len() as u32);
+        self.doc_lens.insert(doc, tokens.len() as u32);
+        self.total_len += tokens.len() as u64;
+    }
+    // Indexes every field on its own, under field-qualified terms, and all
+    // fields together as the document's unqualified terms.
+    pub fn add_document(&mut self, document: &Document, doc: u32) {
+        self.remove_doc(doc);
+        let mut by_field: Vec<(&str, Positioned)> = Vec::new();
+        let mut all: Positioned = Vec::new();
+        let mut offset = 0;
+        for field in &document.fields {
+            let at = match by_field.iter().position(|f| f.0 == field.name) {
+                Some(at) => at,
+                None => {
+                    by_field.push((&field.name, Vec::new()));
+                    by_field.len() - 1
+                }
+            };
+            let field_offset = by_field[at].1.len() as u32;
+            for (pos, token) in field.tokens.iter().enumerate() {
+                by_field[at].1.push((field_offset + pos as u32, token));
+                all.push((offset + pos as u32, token));
+            }
+            offset += field.tokens.len() as u32 + POSITION_GAP;
+        }
+        for (name, positioned) in by_field {
+            let len = positioned.len() as u32;
+            self.index_tokens(doc, Some(name), &positioned, len);
+            let stats = self.fields.entry(String::from(name)).or_default();
+            stats.lens.insert(doc, len);
+            stats.total_len += len as u64;
+        }
+        self.index_tokens(doc, None, &all, document.len() as u32);
+        self.doc_lens.insert(doc, document.len() as u32);
+        self.total_len += document.len() as u64;
+        if !document.stored.is_empty() {
+            self.stored.insert(doc, document.stored.clone());
+        }
+    }
+    // Stores a field value for an indexed doc, replacing any earlier value.
+    // Re-adding or removing the doc drops its stored fields.
+    pub fn store(&mut self, doc: u32, field: &str, value: Stored) {
+        let values = self.stored.entry(doc).or_default();
+        match values.iter_mut().find(|v| v.0 == field) {
+            Some(v) => v.1 = value,
+            None => values.push((String::from(field), value))
+        }
+    }
+    pub fn get_stored(&self, doc: u32, field: &str) -> Option<&Stored> {
+        self.stored.get(&doc).and_then(|values| values.iter().find(|v| v.0 == field)).map(|v| &v.1)
+    }
+    // Unqualified tokens containing NUL still count towards the length but
+    // are not indexed, as NUL marks field-qualified keys in the lexicon.
+    fn index_tokens(&mut self, doc: u32, field: Option<&str>, tokens: &[(u32,&[u8])], len: u32) {
+        let mut token_positions: HashMap<&[u8],Vec<u32>> = HashMap::new();
+        for &(pos, token) in tokens {
+            if field.is_none() && token.contains(&0) {
+                continue;
+            }
+            token_positions.entry(token).or_default().push(pos);
+        }
+        for (token, positions) in token_positions {
+            let freq = positions.len() as u32;
+            let s = match field {
+                Some(name) => self.lexicon.intern(&field_term(name, token)),
+                None => self.lexicon.intern(token)
+            };
+            let docs = self.dictionary.entry(s).or_default();
+            let at = docs.binary_search(&doc).unwrap_or_else(|i| i);
+            docs.insert(at, doc);
+            self.tfs.entry(s).or_default().insert(at, freq);
+            self.positions.entry(s).or_default().insert(at, positions);
             let x = self.idfs.entry(s).or_insert(0);
             *x += 1;
-            let w = self.tws.entry(doc).or_insert(0.0);
-            *w += (freq as f32 * *x as f32).powi(2);
+            let w = match field {
+                Some(name) => self.fields.entry(String::from(name)).or_default().norms.entry(doc).or_insert(0.0),
+                None => self.tws.entry(doc).or_insert(0.0)
+            };
+            *w += (freq as f32).powi(2);
+            self.doc_terms.entry(doc).or_default().push(s);
+            self.update_bounds(s, freq, len);
+        }
+    }
+    fn update_bounds(&mut self, s: TermId, tf: u32, len: u32) {
+        let b = self.term_bounds.entry(s).or_insert((tf, len));
+        *b = (b.0.max(tf), b.1.min(len));
+    }
+    pub fn update_doc<T: AsRef<[u8]>>(&mut self, tokens: &[T], doc: u32) {
+        self.add_doc(tokens, doc);
+    }
+    // Removes the doc from every postings list it appears in. Returns false
+    // if the doc was not indexed. Terms left without postings stay in the
+    // lexicon with a df of 0 and are dropped when the index is saved.
+    pub fn remove_doc(&mut self, doc: u32) -> bool {
+        self.stored.remove(&doc);
+        // an empty doc has a length but no terms
+        if !self.doc_lens.contains_key(&doc) {
+            return false;
+        }
+        for s in self.doc_terms.remove(&doc).unwrap_or_default() {
+            let docs = self.dictionary.get_mut(&s).unwrap();
+            if let Ok(at) = docs.binary_search(&doc) {
+                docs.remove(at);
+                self.tfs.get_mut(&s).unwrap().remove(at);
+                self.positions.get_mut(&s).unwrap().remove(at);
+                *self.idfs.get_mut(&s).unwrap() -= 1;
+            }
+        }
+        self.tws.remove(&doc);
+        if let Some(len) = self.doc_lens.remove(&doc) {
+            self.total_len -= len as u64;
+        }
+        for stats in self.fields.values_mut() {
+            if let Some(len) = stats.lens.remove(&doc) {
+                stats.total_len -= len as u64;
+            }
+            stats.norms.remove(&doc);
+        }
+        true
+    }
+    pub fn field_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.fields.keys().map(|f| f.as_str()).collect();
+        names.sort();
+        names
+    }
+    pub fn get_field_len(&self, field: &str, doc: u32) -> u32 {
+        self.fields.get(field).and_then(|f| f.lens.get(&doc).cloned()).unwrap_or(0)
+    }
+    pub fn field_collection_stats(&self, field: &str) -> CollectionStats {
+        match self.fields.get(field) {
+            Some(f) if !f.lens.is_empty() => CollectionStats {
+                num_docs: f.lens.len() as u32,
+                total_len: f.total_len,
+                avg_doc_len: f.total_len as f64 / f.lens.len() as f64
+            },
+            _ => CollectionStats { num_docs: 0, total_len: 0, avg_doc_len: 0.0 }
+        }
+    }
+    pub fn get_field_doc_stats(&self, field: &str, doc: u32) -> DocStats {
+        let norm = self.fields.get(field).and_then(|f| f.norms.get(&doc)).map_or(0.0, |w| (*w as f64).sqrt());
+        DocStats {
+            len: self.get_field_len(field, doc),
+            norm
+        }
+    }
+    pub fn get_field_docs(&self, field: &str, token: &[u8]) -> &Vec<u32> {
+        self.get_docs(&field_term(field, token))
+    }
+    pub fn get_term_stats(&self, token: &[u8]) -> TermStats {
+        TermStats {
+            df: self.get_idf(token),
+            cf: self.get_tfs(token).iter().map(|&tf| tf as u64).sum()
+        }
+    }
+    pub fn get_doc_stats(&self, doc: u32) -> DocStats {
+        DocStats {
+            len: self.get_doc_len(doc),
+            norm: self.tws.get(&doc).map_or(0.0, |w| (*w as f64).sqrt())
         }
     }
     pub fn get_ranking(&self, query: &[Vec<u8>]) -> HashMap<u32,f64> {
-        let mut weights: HashMap<u32,u64> = HashMap::new();
+        self.get_ranking_with(query, &TfIdf)
+    }
+    pub fn get_ranking_with<S: Scorer + ?Sized>(&self, query: &[Vec<u8>], scorer: &S) -> HashMap<u32,f64> {
+        let keys = count_terms(query.iter().cloned());
+        self.rank_terms(&keys, &self.collection_stats(), |d| self.get_doc_stats(d), scorer)
+    }
+    // Scores each field on its own and sums the scores weighted by the
+    // field's boost, e.g. `&[("title", 2.0), ("body", 1.0)]`.
+    pub fn get_field_ranking<S: Scorer + ?Sized>(&self, query: &[Vec<u8>], boosts: &[(&str, f64)], scorer: &S) -> HashMap<u32,f64> {
         let mut rankings: HashMap<u32,f64> = HashMap::new();
-        let mut token_freqs: HashMap<&[u8],u32> = HashMap::new();
-        let mut query_weight = 0.0;
-        for token in query {
-            let t = token_freqs.entry(token).or_insert(0);
-            *t += 1;
+        for &(field, boost) in boosts {
+            let keys = count_terms(query.iter().map(|t| field_term(field, t)));
+            let coll = self.field_collection_stats(field);
+            for (d, w) in self.rank_terms(&keys, &coll, |d| self.get_field_doc_stats(field, d), scorer) {
+                *rankings.entry(d).or_insert(0.0) += boost * w;
+            }
         }
-        for (token, freq) in token_freqs {
-            let idf = self.get_idf(&token);
-            query_weight += (freq as f32).powi(2);
+        rankings
+    }
+    pub fn get_ranking_bm25f(&self, query: &[Vec<u8>], bm25f: &Bm25F) -> HashMap<u32,f64> {
+        let mut rankings: HashMap<u32,f64> = HashMap::new();
+        let coll = self.collection_stats();
+        for (token, freq) in count_terms(query.iter().cloned()) {
+            let term = self.get_term_stats(&token);
+            if term.df == 0 {
+                continue;
+            }
+            let mut weighted_tfs: HashMap<u32,f64> = HashMap::new();
+            for field in &bm25f.fields {
+                let key = field_term(&field.name, &token);
+                let avg_len = self.field_collection_stats(&field.name).avg_doc_len;
+                for (d, tf) in self.get_docs(&key).iter().zip(self.get_tfs(&key).iter()) {
+                    let len = self.get_field_len(&field.name, *d);
+                    *weighted_tfs.entry(*d).or_insert(0.0) += bm25f.field_tf(field, *tf, len, avg_len);
+                }
+            }
+            for (d, wtf) in weighted_tfs {
+                *rankings.entry(d).or_insert(0.0) += freq as f64 * bm25f.score(wtf, &term, &coll);
+            }
+        }
+        rankings
+    }
+    fn rank_terms<S, D>(&self, keys: &[(Vec<u8>,u32)], coll: &CollectionStats, doc_stats: D, scorer: &S) -> HashMap<u32,f64>
+        where S: Scorer + ?Sized, D: Fn(u32) -> DocStats
+    {
+        let mut weights: HashMap<u32,f64> = HashMap::new();
+        let mut query_weight = 0.0;
+        for &(ref token, freq) in keys {
+            let term = self.get_term_stats(token);
+            if term.df == 0 {
+                continue;
+            }
+            query_weight += scorer.query_weight(freq, &term, coll).powi(2);
             for (d, tf) in self.get_docs(token).iter().zip(self.get_tfs(token).iter()) {
-                let w = weights.entry(*d).or_insert(0);
-                *w += (tf * idf) as u64;
+                let w = weights.entry(*d).or_insert(0.0);
+                *w += scorer.score(*tf, freq, &doc_stats(*d), &term, coll);
             }
         };
-        query_weight = query_weight.sqrt();
-        for (d, w) in weights {
-            rankings.insert(d, w as f64 / (query_weight as f64 * self.tws[&d].sqrt() as f64) as f64);
-        };
-        rankings
+        let query_norm = f64::sqrt(query_weight);
+        weights.into_iter()
+            .map(|(d, w)| (d, scorer.finish(w, &doc_stats(d), query_norm)))
+            .collect()
+    }
+    pub fn all_docs(&self) -> Vec<u32> {
+        let mut docs: Vec<u32> = self.doc_lens.keys().cloned().collect();
+        docs.sort();
+        docs
+    }
+    // Sorted ids of the documents matching a boolean query.
+    pub fn get_query_docs(&self, query: &Query) -> Vec<u32> {
+        match *query {
+            Query::Term(ref t) => self.get_docs(t).clone(),
+            Query::Field(ref name, ref q) => self.get_query_docs(&q.in_field(name)),
+            Query::Phrase(ref p) => {
+                let mut docs: Vec<u32> = self.get_phrase(p).keys().cloned().collect();
+                docs.sort();
+                docs
+            },
+            Query::And(ref qs) => {
+                let (negated, positive): (Vec<&Query>, Vec<&Query>) = qs.iter().partition(|q| matches!(**q, Query::Not(_)));
+                let matched = if positive.is_empty() {
+                    self.all_docs()
+                } else {
+                    intersect_all(positive.into_iter().map(|q| self.get_query_docs(q)).collect())
+                };
+                negated.into_iter().fold(matched, |docs, q| match *q {
+                    Query::Not(ref inner) => difference(&docs, &self.get_query_docs(inner)),
+                    _ => docs
+                })
+            },
+            Query::Or(ref qs) => union_all(qs.iter().map(|q| self.get_query_docs(q)).collect()),
+            Query::Not(ref q) => difference(&self.all_docs(), &self.get_query_docs(q)),
+            Query::Bool { ref must, ref should, ref must_not } => {
+                let matched = if must.is_empty() {
+                    union_all(should.iter().map(|q| self.get_query_docs(q)).collect())
+                } else {
+                    intersect_all(must.iter().map(|q| self.get_query_docs(q)).collect())
+                };
+                let excluded = union_all(must_not.iter().map(|q| self.get_query_docs(q)).collect());
+                difference(&matched, &excluded)
+            }
+        }
+    }
+    // Matching documents ordered by score, best first. Documents are scored
+    // on the query's non-excluded terms.
+    pub fn get_query_ranking<S: Scorer + ?Sized>(&self, query: &Query, scorer: &S) -> Vec<(u32,f64)> {
+        let docs = self.get_query_docs(query);
+        let mut weights = vec![0.0; docs.len()];
+        let mut query_weight = 0.0;
+        let coll = self.collection_stats();
+        let mut terms: HashMap<(Option<String>,Vec<u8>),u32> = HashMap::new();
+        for term in query.scoring_terms() {
+            *terms.entry(term).or_insert(0) += 1;
+        }
+        // field-qualified terms are scored against their field's statistics
+        for ((field, token), freq) in terms {
+            let token = match field {
+                Some(ref name) => field_term(name, &token),
+                None => token
+            };
+            let term = self.get_term_stats(&token);
+            if term.df == 0 {
+                continue;
+            }
+            let term_coll = match field {
+                Some(ref name) => self.field_collection_stats(name),
+                None => coll
+            };
+            query_weight += scorer.query_weight(freq, &term, &term_coll).powi(2);
+            let (postings, tfs) = (self.get_docs(&token), self.get_tfs(&token));
+            let mut at = 0;
+            for (i, d) in docs.iter().enumerate() {
+                at = gallop(postings, at, *d);
+                if at == postings.len() {
+                    break;
+                }
+                if postings[at] == *d {
+                    let doc = match field {
+                        Some(ref name) => self.get_field_doc_stats(name, *d),
+                        None => self.get_doc_stats(*d)
+                    };
+                    weights[i] += scorer.score(tfs[at], freq, &doc, &term, &term_coll);
+                }
+            }
+        }
+        let query_norm = f64::sqrt(query_weight);
+        let mut ranking: Vec<(u32,f64)> = docs.into_iter()
+            .zip(weights)
+            .map(|(d, w)| {
+                let score = if w == 0.0 { 0.0 } else { scorer.finish(w, &self.get_doc_stats(d), query_norm) };
+                (d, score)
+            })
+            .collect();
+        ranking.sort_by(|a, b| cmp_scores(b.1, a.1).then(a.0.cmp(&b.0)));
+        ranking
+    }
+    // The k best documents for a bag of terms, best first.
+    pub fn search(&self, query: &[Vec<u8>], k: usize) -> Vec<(u32,f64)> {
+        self.search_with(query, k, &TfIdf)
+    }
+    // Same ranking as `get_ranking_with` truncated to k, but found with WAND
+    // so documents that cannot reach the top k are skipped unscored.
+    pub fn search_with<S: Scorer + ?Sized>(&self, query: &[Vec<u8>], k: usize, scorer: &S) -> Vec<(u32,f64)> {
+        let mut token_freqs: HashMap<&[u8],u32> = HashMap::new();
+        for token in query {
+            *token_freqs.entry(token).or_insert(0) += 1;
+        }
+        let coll = self.collection_stats();
+        let terms: Vec<(TermId, u32, TermStats)> = token_freqs.into_iter()
+            .filter_map(|(token, freq)| self.get_term_id(token).map(|s| (s, freq, self.get_term_stats(token))))
+            .filter(|t| t.2.df > 0)
+            .collect();
+        let query_norm = terms.iter()
+            .map(|&(_, freq, ref term)| scorer.query_weight(freq, term, &coll).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        let cursors = terms.iter()
+            .map(|&(s, freq, term)| {
+                let (max_tf, min_len) = self.term_bounds[&s];
+                let bound = scorer.max_score(max_tf, min_len, freq, &term, &coll, query_norm);
+                TermCursor::new(&self.dictionary[&s], &self.tfs[&s], freq, term, bound)
+            })
+            .collect();
+        wand(cursors, k, |d, matched| {
+            let doc = self.get_doc_stats(d);
+            let w = matched.iter().map(|&(tf, freq, ref term)| scorer.score(tf, freq, &doc, term, &coll)).sum();
+            scorer.finish(w, &doc, query_norm)
+        })
+    }
+    pub fn get_phrase(&self, phrase: &[Vec<u8>]) -> HashMap<u32,u32> {
+        self.match_positions(phrase, count_phrase)
+    }
+    // terms in any order, at most `slop` positions beyond adjacent
+    pub fn get_near(&self, terms: &[Vec<u8>], slop: u32) -> HashMap<u32,u32> {
+        let max_span = terms.len().saturating_sub(1) as u32 + slop;
+        self.match_positions(terms, |p| count_unordered(p, max_span))
+    }
+    // terms in query order, at most `slop` positions beyond adjacent
+    pub fn get_ordered_near(&self, terms: &[Vec<u8>], slop: u32) -> HashMap<u32,u32> {
+        let max_span = terms.len().saturating_sub(1) as u32 + slop;
+        self.match_positions(terms, |p| count_ordered(p, max_span))
+    }
+    fn match_positions<F>(&self, terms: &[Vec<u8>], count: F) -> HashMap<u32,u32>
+        where F: Fn(&[&[u32]]) -> u32
+    {
+        let mut matches = HashMap::new();
+        if terms.is_empty() {
+            return matches;
+        }
+        let by_doc: Vec<HashMap<u32,&[u32]>> = terms.iter()
+            .map(|t| {
+                self.get_docs(t).iter()
+                    .zip(self.get_positions(t).iter())
+                    .map(|(d, p)| (*d, p.as_slice()))
+                    .collect()
+            })
+            .collect();
+        for d in by_doc[0].keys() {
+            let doc_positions: Option<Vec<&[u32]>> = by_doc.iter().map(|m| m.get(d).cloned()).collect();
+            if let Some(doc_positions) = doc_positions {
+                let n = count(&doc_positions);
+                if n > 0 {
+                    matches.insert(*d, n);
+                }
+            }
+        }
+        matches
+    }
+    // Writes the index as a single segment file, see `segment` for the layout.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut ids: Vec<TermId> = self.dictionary.iter()
+            .filter(|&(_, docs)| !docs.is_empty())
+            .map(|(s, _)| *s)
+            .collect();
+        ids.sort_by(|a, b| self.lexicon.term(*a).cmp(self.lexicon.term(*b)));
+        let terms: Vec<(&[u8], Vec<Posting>)> = ids.into_iter()
+            .map(|s| {
+                let postings: Vec<Posting> = self.dictionary[&s].iter()
+                    .zip(self.tfs[&s].iter())
+                    .zip(self.positions[&s].iter())
+                    .map(|((d, tf), p)| Posting { doc: *d, tf: *tf, positions: p.clone() })
+                    .collect();
+                (self.lexicon.term(s), postings)
+            })
+            .collect();
+        let mut docs: Vec<DocEntry> = self.doc_lens.iter()
+            .map(|(d, len)| DocEntry { doc: *d, len: *len, weight: self.tws.get(d).cloned().unwrap_or(0.0) })
+            .collect();
+        docs.sort_by_key(|d| d.doc);
+        let fields: Vec<(&str, Vec<DocEntry>)> = self.field_names().into_iter()
+            .map(|name| {
+                let stats = &self.fields[name];
+                let mut entries: Vec<DocEntry> = stats.lens.iter()
+                    .map(|(d, len)| DocEntry { doc: *d, len: *len, weight: stats.norms.get(d).cloned().unwrap_or(0.0) })
+                    .collect();
+                entries.sort_by_key(|d| d.doc);
+                (name, entries)
+            })
+            .collect();
+
+        let mut stored: Vec<(u32, &[(String, Stored)])> = self.stored.iter()
+            .map(|(d, values)| (*d, values.as_slice()))
+            .collect();
+        stored.sort_by_key(|s| s.0);
+
+        let mut out = BufWriter::new(File::create(path)?);
+        write_segment(&mut out, &terms, &docs, &fields, &stored, self.total_len)
+    }
+    // Loads a whole segment back into memory. Use `Segment::open` to query a
+    // segment without reading all of its postings.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SegmentError> {
+        let segment = Segment::open(path)?;
+        let mut ii = InvertedIndex::new();
+        for i in 0..segment.num_terms() {
+            let s = ii.lexicon.intern(segment.term(i));
+            let postings = segment.postings(i)?;
+            ii.idfs.insert(s, postings.len() as u32);
+            let docs = ii.dictionary.entry(s).or_default();
+            let tfs = ii.tfs.entry(s).or_default();
+            let positions = ii.positions.entry(s).or_default();
+            for p in postings {
+                ii.doc_terms.entry(p.doc).or_default().push(s);
+                docs.push(p.doc);
+                tfs.push(p.tf);
+                positions.push(p.positions);
+            }
+        }
+        for i in 0..segment.num_docs() {
+            let d = segment.doc_entry_at(i).ok_or(SegmentError::Corrupt("document entry out of range"))?;
+            ii.doc_lens.insert(d.doc, d.len);
+            ii.tws.insert(d.doc, d.weight);
+        }
+        ii.total_len = segment.total_len();
+        for (name, entries) in segment.fields()? {
+            let stats = ii.fields.entry(name).or_default();
+            for d in entries {
+                stats.lens.insert(d.doc, d.len);
+                stats.norms.insert(d.doc, d.weight);
+                stats.total_len += d.len as u64;
+            }
+        }
+        ii.stored = segment.stored()?.into_iter().collect();
+        let ids: Vec<TermId> = ii.dictionary.keys().cloned().collect();
+        for s in ids {
+            // field-qualified terms are bounded by their field's lengths
+            let field = {
+                let term = ii.lexicon.term(s);
+                term.iter().position(|&b| b == 0).and_then(|i| ::std::str::from_utf8(&term[..i]).ok().map(String::from))
+            };
+            for i in 0..ii.dictionary[&s].len() {
+                let d = ii.dictionary[&s][i];
+                let len = match field {
+                    Some(ref name) if ii.fields.contains_key(name) => ii.get_field_len(name, d),
+                    _ => *ii.doc_lens.get(&d).ok_or(SegmentError::Corrupt("posting for a document with no entry"))?
+                };
+                let tf = ii.tfs[&s][i];
+                ii.update_bounds(s, tf, len);
+            }
+        }
+        Ok(ii)
     }
     pub fn print_internal(&self) {
+        println!("{:?}", self.lexicon.terms());
         println!("{:?}", self.dictionary);
         println!("{:?}", self.tfs);
+        println!("{:?}", self.positions);
         println!("{:?}", self.idfs);
         println!("{:?}", self.tws);
+        println!("{:?}", self.doc_lens);
+        println!("{:?}", self.doc_terms);
+    }
+}
+
+fn count_terms<I: Iterator<Item = Vec<u8>>>(terms: I) -> Vec<(Vec<u8>,u32)> {
+    let mut token_freqs: HashMap<Vec<u8>,u32> = HashMap::new();
+    for token in terms {
+        *token_freqs.entry(token).or_insert(0) += 1;
     }
+    token_freqs.into_iter().collect()
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use std::path::PathBuf;
 
     #[test]
     fn get_docs_at() {
@@ -129,4 +672,516 @@ mod tests {
         assert!(&ii.get_ranking(&query)[&42] > &0.0f64);
 
     }
+
+    #[test]
+    fn single_byte_terms_stay_separate() {
+        let mut ii = InvertedIndex::new();
+
+        // every one-byte token used to hash to 0
+        ii.add_doc(&[b"a".to_vec()], 1);
+        ii.add_doc(&[b"b".to_vec(), b"b".to_vec()], 2);
+
+        assert_eq!(ii.get_docs(b"a"), &vec![1]);
+        assert_eq!(ii.get_docs(b"b"), &vec![2]);
+        assert_eq!(ii.get_tfs(b"b"), &vec![2]);
+        assert_eq!(ii.get_idf(b"a"), 1);
+        assert!(ii.get_docs(b"c").is_empty());
+    }
+
+    #[test]
+    fn colliding_terms_stay_separate() {
+        let mut ii = InvertedIndex::new();
+
+        // "ab" and "cb" both summed to 98 under the position-weighted hash
+        ii.add_doc(&[b"ab".to_vec()], 1);
+        ii.add_doc(&[b"cb".to_vec()], 2);
+
+        assert_eq!(ii.get_docs(b"ab"), &vec![1]);
+        assert_eq!(ii.get_docs(b"cb"), &vec![2]);
+        assert_eq!(ii.get_idf(b"cb"), 1);
+
+        let rankings = ii.get_ranking(&[b"ab".to_vec()]);
+
+        assert_eq!(rankings.len(), 1);
+        assert!(rankings.contains_key(&1));
+    }
+
+    #[test]
+    fn tracks_doc_lengths() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&[b"grand".to_vec(), b"jury".to_vec(), b"jury".to_vec()], 1);
+        ii.add_doc(&[b"jury".to_vec()], 2);
+
+        assert_eq!(ii.get_doc_len(1), 3);
+        assert_eq!(ii.get_doc_len(2), 1);
+        assert_eq!(ii.avg_doc_len(), 2.0);
+        assert_eq!(ii.get_term_stats(b"jury"), TermStats { df: 2, cf: 3 });
+    }
+
+    #[test]
+    fn ranking_with_scorers() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&[b"grand".to_vec(), b"jury".to_vec(), b"said".to_vec()], 1);
+        ii.add_doc(&[b"jury".to_vec(), b"said".to_vec(), b"friday".to_vec()], 2);
+        ii.add_doc(&[b"county".to_vec(), b"said".to_vec(), b"friday".to_vec()], 3);
+
+        let query = vec![b"grand".to_vec(), b"jury".to_vec()];
+        let scorers: Vec<Box<dyn Scorer>> = vec![
+            Box::new(TfIdf),
+            Box::new(Bm25::new(1.5, 0.5)),
+            Box::new(LmDirichlet::new(10.0)),
+            Box::new(LmJelinekMercer::default()),
+            Box::new(Dfr::default())
+        ];
+
+        for scorer in scorers {
+            let rankings = ii.get_ranking_with(&query, scorer.as_ref());
+
+            assert_eq!(rankings.len(), 2);
+            assert!(rankings[&1] > rankings[&2]);
+        }
+    }
+
+    fn words(s: &str) -> Vec<Vec<u8>> {
+        s.split(' ').map(|w| w.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn phrase_queries() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the fulton county grand jury said friday"), 1);
+        ii.add_doc(&words("the jury said the grand old party"), 2);
+
+        assert_eq!(ii.get_positions(b"jury"), &vec![vec![4], vec![1]]);
+
+        let phrase = ii.get_phrase(&words("grand jury"));
+
+        assert_eq!(phrase.len(), 1);
+        assert_eq!(phrase[&1], 1);
+        assert!(ii.get_phrase(&words("grand county")).is_empty());
+        assert!(ii.get_phrase(&words("grand verdict")).is_empty());
+    }
+
+    #[test]
+    fn proximity_queries() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the fulton county grand jury said friday"), 1);
+        ii.add_doc(&words("the jury said the grand old party"), 2);
+
+        assert_eq!(ii.get_near(&words("grand jury"), 0).len(), 1);
+        assert_eq!(ii.get_near(&words("grand jury"), 2).len(), 2);
+        assert_eq!(ii.get_ordered_near(&words("grand jury"), 2).len(), 1);
+        assert_eq!(ii.get_ordered_near(&words("jury grand"), 2).len(), 1);
+        assert!(ii.get_ordered_near(&words("jury grand"), 2).contains_key(&2));
+        // doc 2 has `the` twice, doc 1 once
+        assert_eq!(ii.get_near(&words("the the"), 5).keys().collect::<Vec<_>>(), vec![&2]);
+    }
+
+    fn temp_path(name: &str) -> ::std::path::PathBuf {
+        ::std::env::temp_dir().join(format!("graffiti-{}-{}.seg", name, ::std::process::id()))
+    }
+
+    fn sample_index() -> InvertedIndex {
+        let mut ii = InvertedIndex::new();
+        ii.add_doc(&words("the fulton county grand jury said friday"), 7);
+        ii.add_doc(&words("the jury said the grand old party"), 3);
+        ii.add_doc(&words("no evidence that any irregularities took place"), 300);
+        ii
+    }
+
+    #[test]
+    fn save_and_open() {
+        let ii = sample_index();
+        let path = temp_path("save-open");
+
+        ii.save(&path).unwrap();
+        let loaded = InvertedIndex::open(&path).unwrap();
+
+        assert_eq!(loaded.num_docs(), 3);
+        assert_eq!(loaded.get_doc_len(300), 7);
+        assert_eq!(loaded.get_idf(b"jury"), 2);
+        assert_eq!(loaded.get_phrase(&words("grand jury")).len(), 1);
+        assert_eq!(loaded.get_ranking_with(&words("grand jury"), &Bm25::default()),
+                   ii.get_ranking_with(&words("grand jury"), &Bm25::default()));
+
+        let segment = Segment::open(&path).unwrap();
+        let postings = segment.get_postings(b"the").unwrap();
+
+        assert_eq!(postings, vec![
+            Posting { doc: 3, tf: 2, positions: vec![0, 3] },
+            Posting { doc: 7, tf: 1, positions: vec![0] }
+        ]);
+        assert!(segment.get_postings(b"verdict").unwrap().is_empty());
+        assert_eq!(segment.get_ranking_with(&words("said the"), &TfIdf).unwrap(),
+                   ii.get_ranking(&words("said the")));
+
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn open_rejects_bad_files() {
+        let ii = sample_index();
+        let path = temp_path("corrupt");
+        ii.save(&path).unwrap();
+        let bytes = ::std::fs::read(&path).unwrap();
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        ::std::fs::write(&path, &bad_version).unwrap();
+        match InvertedIndex::open(&path) {
+            Err(SegmentError::UnsupportedVersion(99)) => {},
+            _ => panic!("expected a version error")
+        }
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        ::std::fs::write(&path, &bad_magic).unwrap();
+        match InvertedIndex::open(&path) {
+            Err(SegmentError::BadMagic) => {},
+            _ => panic!("expected a magic error")
+        }
+
+        // flip a bit in the last postings block
+        let mut bad_postings = bytes.clone();
+        let last = bad_postings.len() - 1;
+        bad_postings[last] ^= 1;
+        ::std::fs::write(&path, &bad_postings).unwrap();
+        let segment = Segment::open(&path).unwrap();
+        let last_term = segment.num_terms() - 1;
+        match segment.postings(last_term) {
+            Err(SegmentError::Checksum("postings")) => {},
+            _ => panic!("expected a postings checksum error")
+        }
+        assert!(InvertedIndex::open(&path).is_err());
+
+        ::std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(InvertedIndex::open(&path).is_err());
+
+        // a posting for a document the doc section does not list
+        let terms: Vec<(&[u8], Vec<Posting>)> = vec![(b"jury", vec![Posting { doc: 5, tf: 1, positions: vec![0] }])];
+        let mut out = File::create(&path).unwrap();
+        write_segment(&mut out, &terms, &[DocEntry { doc: 1, len: 1, weight: 1.0 }], &[], &[], 1).unwrap();
+        drop(out);
+        match InvertedIndex::open(&path) {
+            Err(SegmentError::Corrupt("posting for a document with no entry")) => {},
+            _ => panic!("expected a missing document entry")
+        }
+
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn re_adding_a_doc_replaces_it() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("grand jury said"), 1);
+        ii.add_doc(&words("jury said friday"), 2);
+        let before = ii.get_ranking(&words("jury friday"));
+
+        ii.add_doc(&words("grand jury said"), 1);
+
+        assert_eq!(ii.num_docs(), 2);
+        assert_eq!(ii.get_idf(b"jury"), 2);
+        assert_eq!(ii.get_docs(b"jury"), &vec![1, 2]);
+        assert_eq!(ii.get_tfs(b"grand"), &vec![1]);
+        assert_eq!(ii.avg_doc_len(), 3.0);
+        assert_eq!(ii.get_ranking(&words("jury friday")), before);
+    }
+
+    #[test]
+    fn add_doc_from_borrowed_tokens() {
+        let mut ii = InvertedIndex::new();
+        let text = b"grand jury said";
+        let tokens: Vec<&[u8]> = text.split(|&b| b == b' ').collect();
+
+        ii.add_doc(&tokens, 1);
+        ii.add_doc(&["the", "jury"], 2);
+
+        assert_eq!(ii.get_docs(b"jury"), &vec![1, 2]);
+        assert_eq!(ii.get_doc_len(1), 3);
+    }
+
+    #[test]
+    fn remove_doc() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("grand jury said"), 1);
+        ii.add_doc(&words("jury said friday friday"), 2);
+
+        assert!(ii.remove_doc(2));
+        assert!(!ii.remove_doc(2));
+        assert!(!ii.contains_doc(2));
+        assert_eq!(ii.get_docs(b"jury"), &vec![1]);
+        assert!(ii.get_docs(b"friday").is_empty());
+        assert_eq!(ii.get_idf(b"friday"), 0);
+        assert_eq!(ii.avg_doc_len(), 3.0);
+        assert!(ii.get_ranking(&words("friday")).is_empty());
+        assert!(ii.get_phrase(&words("said friday")).is_empty());
+    }
+
+    #[test]
+    fn remove_empty_doc() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("grand jury"), 1);
+        ii.add_doc(&words(""), 7);
+
+        assert!(ii.contains_doc(7));
+        assert_eq!(ii.num_docs(), 2);
+        assert!(ii.remove_doc(7));
+        assert!(!ii.contains_doc(7));
+        assert_eq!(ii.num_docs(), 1);
+        assert_eq!(ii.avg_doc_len(), 2.0);
+    }
+
+    #[test]
+    fn raw_tokens_with_nul() {
+        let mut ii = InvertedIndex::new();
+        let path = temp_path("nul");
+
+        ii.add_document(&Document::new().field("title", words("grand jury")).unwrap(), 1);
+        ii.add_doc(&[b"title\0jury".to_vec(), b"said".to_vec()], 2);
+
+        assert_eq!(ii.get_field_docs("title", b"jury"), &vec![1]);
+        assert_eq!(ii.get_doc_len(2), 2);
+        assert_eq!(ii.get_docs(b"said"), &vec![2]);
+
+        ii.save(&path).unwrap();
+        let loaded = InvertedIndex::open(&path).unwrap();
+
+        assert_eq!(loaded.get_field_docs("title", b"jury"), &vec![1]);
+        assert_eq!(loaded.get_doc_len(2), 2);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn update_doc() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("grand jury said"), 5);
+        ii.add_doc(&words("county jury"), 1);
+        ii.update_doc(&words("grand old party"), 5);
+
+        assert_eq!(ii.get_docs(b"jury"), &vec![1]);
+        assert_eq!(ii.get_docs(b"party"), &vec![5]);
+        assert_eq!(ii.get_phrase(&words("grand old")).len(), 1);
+        assert_eq!(ii.get_doc_len(5), 3);
+
+        let path = temp_path("update");
+        ii.save(&path).unwrap();
+        let loaded = InvertedIndex::open(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get_docs(b"said"), &Vec::<u32>::new());
+        assert_eq!(loaded.get_docs(b"grand"), &vec![5]);
+    }
+
+    #[test]
+    fn boolean_queries() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the fulton county grand jury said friday"), 1);
+        ii.add_doc(&words("the jury said the grand old party"), 2);
+        ii.add_doc(&words("the petit jury said nothing"), 3);
+        ii.add_doc(&words("no evidence that any irregularities took place"), 4);
+
+        let docs = |q: &str| ii.get_query_docs(&Query::parse(q).unwrap());
+
+        assert_eq!(docs("grand AND jury"), vec![1, 2]);
+        assert_eq!(docs("grand OR evidence"), vec![1, 2, 4]);
+        assert_eq!(docs("jury AND NOT county"), vec![2, 3]);
+        assert_eq!(docs("NOT jury"), vec![4]);
+        assert_eq!(docs("\"grand jury\""), vec![1]);
+        assert_eq!(docs("+jury -grand said"), vec![3]);
+        assert_eq!(docs("(grand OR petit) AND said -\"grand jury\""), vec![2, 3]);
+        assert!(docs("verdict AND jury").is_empty());
+    }
+
+    #[test]
+    fn boolean_query_ranking() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the fulton county grand jury said friday"), 1);
+        ii.add_doc(&words("the jury said the grand old party"), 2);
+        ii.add_doc(&words("the petit jury said nothing"), 3);
+
+        let ranking = ii.get_query_ranking(&Query::parse("+jury county -petit").unwrap(), &Bm25::default());
+
+        assert_eq!(ranking.len(), 2);
+        assert_eq!(ranking[0].0, 1);
+        assert!(ranking[0].1 > ranking[1].1);
+    }
+
+    #[test]
+    fn nan_scores() {
+        // NaN for documents of three words, the length for the others
+        struct NanScorer;
+
+        impl Scorer for NanScorer {
+            fn score(&self, _: u32, _: u32, doc: &DocStats, _: &TermStats, _: &CollectionStats) -> f64 {
+                if doc.len == 3 { f64::NAN } else { doc.len as f64 }
+            }
+        }
+
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the grand jury"), 2);
+        ii.add_doc(&words("the petit jury"), 1);
+        ii.add_doc(&words("jury"), 4);
+        ii.add_doc(&words("the jury said so"), 3);
+        ii.add_doc(&words("jury said"), 5);
+
+        let ranking = ii.get_query_ranking(&Query::parse("jury").unwrap(), &NanScorer);
+
+        assert_eq!(ranking.iter().map(|r| r.0).collect::<Vec<_>>(), vec![3, 5, 4, 1, 2]);
+    }
+
+    #[test]
+    fn top_k_search() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_doc(&words("the fulton county grand jury said friday"), 1);
+        ii.add_doc(&words("the jury said the grand old party"), 2);
+        ii.add_doc(&words("the petit jury said nothing"), 3);
+        ii.add_doc(&words("no evidence that any irregularities took place"), 4);
+        ii.add_doc(&words("grand jury grand jury"), 5);
+        ii.add_doc(&words("the jury took the evidence"), 6);
+        ii.remove_doc(6);
+
+        let query = words("grand jury evidence the");
+        let scorers: Vec<Box<dyn Scorer>> = vec![
+            Box::new(TfIdf),
+            Box::new(Bm25::default()),
+            Box::new(LmDirichlet::new(10.0)),
+            Box::new(LmJelinekMercer::default()),
+            Box::new(Dfr::default())
+        ];
+
+        for scorer in scorers {
+            let mut expected: Vec<(u32,f64)> = ii.get_ranking_with(&query, scorer.as_ref()).into_iter().collect();
+            expected.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+
+            for k in 1..6 {
+                let top = ii.search_with(&query, k, scorer.as_ref());
+
+                assert_eq!(top.len(), k.min(expected.len()));
+                for (hit, exp) in top.iter().zip(expected.iter()) {
+                    assert!((hit.1 - exp.1).abs() < 1e-9);
+                }
+            }
+        }
+        assert_eq!(ii.search(&query, 1)[0].0, 5);
+        assert!(ii.search(&words("verdict"), 3).is_empty());
+    }
+
+    fn brown_document(category: &str, title: &str, body: &str) -> Document {
+        Document::new()
+            .field("category", words(category)).unwrap()
+            .field("title", words(title)).unwrap()
+            .field("body", words(body)).unwrap()
+    }
+
+    #[test]
+    fn multi_field_documents() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_document(&brown_document("news", "grand jury report", "the jury said friday"), 1);
+        ii.add_document(&brown_document("editorial", "county politics", "a grand jury said"), 2);
+
+        assert_eq!(ii.field_names(), vec!["body", "category", "title"]);
+        assert_eq!(ii.get_docs(b"jury"), &vec![1, 2]);
+        assert_eq!(ii.get_field_docs("title", b"jury"), &vec![1]);
+        assert_eq!(ii.get_field_len("title", 2), 2);
+        assert_eq!(ii.get_doc_len(1), 8);
+        assert_eq!(ii.field_collection_stats("body").avg_doc_len, 4.0);
+        // phrases do not run from one field into the next
+        assert!(ii.get_phrase(&words("report the")).is_empty());
+        assert_eq!(ii.get_phrase(&words("grand jury")).len(), 2);
+
+        let docs = |q: &str| ii.get_query_docs(&Query::parse(q).unwrap());
+
+        assert_eq!(docs("title:jury"), vec![1]);
+        assert_eq!(docs("body:\"grand jury\""), vec![2]);
+        assert_eq!(docs("jury -category:news"), vec![2]);
+        assert_eq!(docs("title:(county OR report) AND body:said"), vec![1, 2]);
+
+        let ranking = ii.get_query_ranking(&Query::parse("title:jury OR body:jury").unwrap(), &Bm25::default());
+
+        assert_eq!(ranking[0].0, 1);
+    }
+
+    #[test]
+    fn field_boosts_and_bm25f() {
+        let mut ii = InvertedIndex::new();
+
+        ii.add_document(&brown_document("news", "grand jury report", "the county said friday"), 1);
+        ii.add_document(&brown_document("news", "county politics", "a grand jury said"), 2);
+
+        let query = words("jury");
+        let title_first = ii.get_field_ranking(&query, &[("title", 5.0), ("body", 1.0)], &Bm25::default());
+        let body_first = ii.get_field_ranking(&query, &[("title", 1.0), ("body", 5.0)], &Bm25::default());
+
+        assert!(title_first[&1] > title_first[&2]);
+        assert!(body_first[&2] > body_first[&1]);
+
+        let bm25f = Bm25F::default().field("title", 5.0, 0.75).field("body", 1.0, 0.75);
+        let ranking = ii.get_ranking_bm25f(&query, &bm25f);
+
+        assert_eq!(ranking.len(), 2);
+        assert!(ranking[&1] > ranking[&2]);
+
+        ii.remove_doc(1);
+
+        assert_eq!(ii.field_collection_stats("title").num_docs, 1);
+        assert!(ii.get_field_docs("title", b"jury").is_empty());
+    }
+
+    #[test]
+    fn save_and_open_fields() {
+        let mut ii = InvertedIndex::new();
+        ii.add_document(&brown_document("news", "grand jury report", "the jury said friday"), 1);
+        ii.add_doc(&words("a plain document"), 2);
+        let path = temp_path("fields");
+
+        ii.save(&path).unwrap();
+        let loaded = InvertedIndex::open(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.field_names(), ii.field_names());
+        assert_eq!(loaded.get_field_len("body", 1), 4);
+        assert_eq!(loaded.get_field_docs("title", b"grand"), &vec![1]);
+        assert_eq!(loaded.field_collection_stats("title"), ii.field_collection_stats("title"));
+        assert_eq!(loaded.get_field_doc_stats("body", 1), ii.get_field_doc_stats("body", 1));
+    }
+
+    #[test]
+    fn stored_fields() {
+        let mut ii = InvertedIndex::new();
+        let report = PathBuf::from("/brown/ca01");
+        ii.add_document(&brown_document("news", "grand jury", "the jury said")
+                        .store("title", Stored::Text(b"Grand Jury".to_vec()))
+                        .store("body", Stored::Offsets { path: report.clone(), start: 10, end: 42 }), 1);
+        ii.add_doc(&words("a plain document"), 2);
+        ii.store(2, "body", Stored::Text(b"A plain document.".to_vec()));
+        ii.add_doc(&words("removed"), 3);
+        ii.store(3, "body", Stored::Text(b"Removed.".to_vec()));
+        ii.remove_doc(3);
+        let path = temp_path("stored");
+
+        ii.save(&path).unwrap();
+        let loaded = InvertedIndex::open(&path).unwrap();
+        ::std::fs::remove_file(&path).unwrap();
+
+        for index in &[&ii, &loaded] {
+            assert_eq!(index.get_stored(1, "title"), Some(&Stored::Text(b"Grand Jury".to_vec())));
+            assert_eq!(index.get_stored(1, "body"), Some(&Stored::Offsets { path: report.clone(), start: 10, end: 42 }));
+            assert_eq!(index.get_stored(2, "body").unwrap().load().unwrap(), b"A plain document.".to_vec());
+            assert!(index.get_stored(2, "title").is_none());
+            assert!(index.get_stored(3, "body").is_none());
+        }
+    }
 }
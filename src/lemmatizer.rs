@@ -0,0 +1,349 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use postagger::*;
+use stemmer::*;
+
+// Reduces words to their dictionary forms, after WordNet's morphy: an
+// irregular form is looked up in the exceptions of its part of speech
+// (`went` is `go` for a verb), and other forms are undone by the
+// detachment rules of their part of speech (`-ches` to `-ch` for a noun,
+// `-ing` for a verb). With a word list, the first rule leaving a known
+// word wins, and a known word is its own lemma; without one, the first
+// rule for the longest suffix that `likely` takes up wins, and a word no
+// rule fits is left as it is.
+//
+// Untagged words are taken as nouns, unless an exception lists them under
+// another part of speech. Words are expected in lower case.
+
+const EXCEPTIONS: &[(Tag, &str, &str)] = &[
+    (Tag::N, "children", "child"), (Tag::N, "feet", "foot"), (Tag::N, "geese", "goose"),
+    (Tag::N, "men", "man"), (Tag::N, "mice", "mouse"), (Tag::N, "people", "person"),
+    (Tag::N, "teeth", "tooth"), (Tag::N, "women", "woman"), (Tag::N, "series", "series"),
+    (Tag::N, "species", "species"), (Tag::N, "lenses", "lens"),
+    (Tag::VB, "am", "be"), (Tag::VB, "are", "be"), (Tag::VB, "is", "be"), (Tag::VB, "was", "be"),
+    (Tag::VB, "were", "be"), (Tag::VB, "been", "be"), (Tag::VB, "has", "have"), (Tag::VB, "had", "have"),
+    (Tag::VB, "did", "do"), (Tag::VB, "does", "do"), (Tag::VB, "done", "do"), (Tag::VB, "went", "go"),
+    (Tag::VB, "gone", "go"), (Tag::VB, "goes", "go"), (Tag::VB, "made", "make"), (Tag::VB, "said", "say"),
+    (Tag::VB, "saw", "see"), (Tag::VB, "seen", "see"), (Tag::VB, "took", "take"), (Tag::VB, "taken", "take"),
+    (Tag::VB, "came", "come"), (Tag::VB, "got", "get"), (Tag::VB, "knew", "know"), (Tag::VB, "known", "know"),
+    (Tag::VB, "thought", "think"), (Tag::VB, "told", "tell"), (Tag::VB, "found", "find"),
+    (Tag::ADJ, "better", "good"), (Tag::ADJ, "best", "good"), (Tag::ADJ, "worse", "bad"),
+    (Tag::ADJ, "worst", "bad"),
+];
+
+// morphy's detachment rules, in its order except that for verbs a bare
+// -ed or -ing comes off before the one restoring an e
+const NOUN_RULES: &[(&str, &str)] = &[
+    ("s", ""), ("ses", "s"), ("xes", "x"), ("zes", "z"), ("ches", "ch"), ("shes", "sh"),
+    ("men", "man"), ("ies", "y"),
+];
+const VERB_RULES: &[(&str, &str)] = &[
+    ("s", ""), ("ies", "y"), ("es", "e"), ("es", ""), ("ed", ""), ("ed", "e"), ("ing", ""), ("ing", "e"),
+];
+const ADJ_RULES: &[(&str, &str)] = &[("er", ""), ("est", ""), ("er", "e"), ("est", "e")];
+
+const TAGS: [Tag; 3] = [Tag::N, Tag::VB, Tag::ADJ];
+
+fn slot(tag: &Tag) -> usize {
+    match *tag {
+        Tag::N => 0,
+        Tag::VB => 1,
+        Tag::ADJ => 2
+    }
+}
+
+fn rules(tag: &Tag) -> &'static [(&'static str, &'static str)] {
+    match *tag {
+        Tag::N => NOUN_RULES,
+        Tag::VB => VERB_RULES,
+        Tag::ADJ => ADJ_RULES
+    }
+}
+
+// The lemma a rule leaves from `stem`, the word without the suffix, when
+// there is no word list to check it, or none if it is unlikely to be a
+// word. Words ending in s after a, s, u or i (gas, glass, bus, this) are
+// not plurals, so -s only comes off after other letters and -ses only
+// leaves such an ending; horses is horse. -zes is more often -ze and an s
+// (sizes), and -men part of the word (specimen). A verb's -es only comes
+// off whole after a sibilant (washes, fixes), and -ed, -ing, -er and -est
+// leave a doubled consonant single (bigger) and put back an e the stem
+// looks to have lost (making, nicer).
+fn likely(suffix: &str, replacement: &str, stem: &[u8]) -> Option<Vec<u8>> {
+    let lemma = [stem, replacement.as_bytes()].concat();
+    let taken = match suffix {
+        "s" => !lemma.last().is_some_and(|b| b"asiu".contains(b)),
+        "ses" => lemma.len() >= 2 && b"siu".contains(&lemma[lemma.len() - 2]),
+        "zes" | "men" => false,
+        "es" => sibilant(stem) == replacement.is_empty(),
+        "ed" | "ing" | "er" | "est" => {
+            if !(0..stem.len()).any(|i| vowel(stem, i)) {
+                return None;
+            }
+            if doubled(stem) {
+                return if replacement.is_empty() { Some(stem[..stem.len() - 1].to_vec()) } else { None };
+            }
+            lost_e(stem) != replacement.is_empty()
+        }
+        _ => true
+    };
+    if taken { Some(lemma) } else { None }
+}
+
+// y is a vowel except at the start of a word
+fn vowel(word: &[u8], i: usize) -> bool {
+    b"aeiou".contains(&word[i]) || i > 0 && word[i] == b'y'
+}
+
+// -es after a single s or z is more often -se or -ze (uses, sizes)
+fn sibilant(stem: &[u8]) -> bool {
+    ["x", "ch", "sh", "ss", "zz"].iter().any(|end| stem.ends_with(end.as_bytes()))
+}
+
+// A consonant doubled after a single vowel, as in bigg and hopp, but not
+// l, s, z or f, which end words doubled (tall, glass).
+fn doubled(stem: &[u8]) -> bool {
+    let n = stem.len();
+    n >= 4 && stem[n - 1] == stem[n - 2] && !vowel(stem, n - 1) && !b"lszf".contains(&stem[n - 1])
+        && vowel(stem, n - 3) && !vowel(stem, n - 4)
+}
+
+// Whether a stem needs an e back: one ending in c, v, rg and the like,
+// which English words seldom do (danc, larg), or a single vowel and a
+// consonant (mak, hop), though not w, x or y.
+fn lost_e(stem: &[u8]) -> bool {
+    let n = stem.len();
+    match stem.last() {
+        Some(b'c') | Some(b'v') => true,
+        Some(b'u') => n >= 2 && !vowel(stem, n - 2),
+        Some(b'g') if n >= 2 && b"dlr".contains(&stem[n - 2]) => true,
+        Some(b) => n >= 2 && !vowel(stem, n - 1) && !b"wxy".contains(b) && vowel(stem, n - 2)
+            && !(0..n - 2).any(|i| vowel(stem, i)),
+        None => false
+    }
+}
+
+pub struct Lemmatizer {
+    // irregular forms and their lemmas, and known lemmas, by tag
+    exceptions: [HashMap<Vec<u8>, Vec<u8>>; 3],
+    words: [HashSet<Vec<u8>>; 3],
+}
+
+impl Default for Lemmatizer {
+    fn default() -> Self {
+        Lemmatizer::new()
+    }
+}
+
+impl Lemmatizer {
+    // A lemmatizer knowing common English irregular forms.
+    pub fn new() -> Self {
+        let mut lemmatizer = Lemmatizer::empty();
+        for &(ref tag, inflected, lemma) in EXCEPTIONS {
+            lemmatizer.add_exception(tag, inflected.as_bytes(), lemma.as_bytes());
+        }
+        lemmatizer
+    }
+    pub fn empty() -> Self {
+        Lemmatizer {
+            exceptions: Default::default(),
+            words: Default::default()
+        }
+    }
+    // Loads the exception lists and word indexes of a WordNet `dict`
+    // directory, `noun.exc` and `index.noun` and so on, where present.
+    pub fn wordnet<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let dir = dir.as_ref();
+        let mut lemmatizer = Lemmatizer::empty();
+        for (tag, name) in TAGS.iter().zip(["noun", "verb", "adj"].iter()) {
+            let exceptions = dir.join(format!("{}.exc", name));
+            if exceptions.exists() {
+                lemmatizer.load_exceptions(tag, exceptions)?;
+            }
+            let index = dir.join(format!("index.{}", name));
+            if index.exists() {
+                lemmatizer.load_words(tag, index)?;
+            }
+        }
+        Ok(lemmatizer)
+    }
+    pub fn add_exception(&mut self, tag: &Tag, inflected: &[u8], lemma: &[u8]) {
+        self.exceptions[slot(tag)].insert(inflected.to_vec(), lemma.to_vec());
+    }
+    // Adds known lemmas; once a tag has any, its rules only leave those.
+    pub fn words<T: AsRef<[u8]>>(mut self, tag: &Tag, words: &[T]) -> Self {
+        self.words[slot(tag)].extend(words.iter().map(|w| w.as_ref().to_vec()));
+        self
+    }
+    // Reads a WordNet exception list, a line per irregular form followed
+    // by its lemmas, as `mice mouse`. The first lemma is taken.
+    pub fn load_exceptions<P: AsRef<Path>>(&mut self, tag: &Tag, path: P) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        for (n, line) in text.lines().enumerate() {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (None, _) => {},
+                (Some(inflected), Some(lemma)) => self.add_exception(tag, inflected.as_bytes(), lemma.as_bytes()),
+                (Some(_), None) => {
+                    let message = format!("line {}: expected an inflected form and its lemma", n + 1);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+        }
+        Ok(())
+    }
+    // Reads the lemmas of a WordNet index file, the first field of each
+    // line. The license at the top of the file is indented and skipped.
+    pub fn load_words<P: AsRef<Path>>(&mut self, tag: &Tag, path: P) -> io::Result<()> {
+        let text = fs::read_to_string(path)?;
+        let words = text.lines()
+            .filter(|line| !line.starts_with(' '))
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|word| word.as_bytes().to_vec());
+        self.words[slot(tag)].extend(words);
+        Ok(())
+    }
+    pub fn lemma<'a>(&self, word: &'a [u8], tag: &Tag) -> Cow<'a, [u8]> {
+        if let Some(lemma) = self.exceptions[slot(tag)].get(word) {
+            return stem_of(word, lemma.clone());
+        }
+        let words = &self.words[slot(tag)];
+        if words.contains(word) {
+            return Cow::Borrowed(word);
+        }
+        let mut candidates: Vec<&(&str, &str)> = rules(tag).iter()
+            .filter(|&&(suffix, _)| word.len() > suffix.len() && word.ends_with(suffix.as_bytes()))
+            .collect();
+        if words.is_empty() {
+            // the sort is stable, so rules for a suffix stay in order
+            candidates.sort_by_key(|&&(suffix, _)| ::std::cmp::Reverse(suffix.len()));
+        }
+        for &&(suffix, replacement) in &candidates {
+            let stem = &word[..word.len() - suffix.len()];
+            if words.is_empty() {
+                if let Some(lemma) = likely(suffix, replacement, stem) {
+                    return stem_of(word, lemma);
+                }
+            } else {
+                let lemma = [stem, replacement.as_bytes()].concat();
+                if words.contains(&lemma) {
+                    return stem_of(word, lemma);
+                }
+            }
+        }
+        Cow::Borrowed(word)
+    }
+    // The lemma of each token, as the tagger tags it, ready for
+    // `InvertedIndex::add_doc`.
+    pub fn lemmatize<T: AsRef<[u8]>>(&self, tokens: &[T], tagger: &PosTagger) -> Vec<Vec<u8>> {
+        tokens.iter()
+            .map(|t| {
+                let t = t.as_ref();
+                match tagger.tag(t) {
+                    Some(tag) => self.lemma(t, tag).into_owned(),
+                    None => self.stem(t).into_owned()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Stem for Lemmatizer {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]> {
+        match TAGS.iter().find(|tag| self.exceptions[slot(tag)].contains_key(word)) {
+            Some(tag) => self.lemma(word, tag),
+            None => self.lemma(word, &Tag::N)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use invertedindex::*;
+
+    #[test]
+    fn lemmas() {
+        let lemmatizer = Lemmatizer::new();
+
+        assert_eq!(lemmatizer.lemma(b"went", &Tag::VB), &b"go"[..]);
+        assert_eq!(lemmatizer.lemma(b"mice", &Tag::N), &b"mouse"[..]);
+        assert_eq!(lemmatizer.lemma(b"churches", &Tag::N), &b"church"[..]);
+        assert_eq!(lemmatizer.lemma(b"ponies", &Tag::N), &b"pony"[..]);
+        assert_eq!(lemmatizer.lemma(b"walked", &Tag::VB), &b"walk"[..]);
+        assert_eq!(lemmatizer.lemma(b"taller", &Tag::ADJ), &b"tall"[..]);
+        assert_eq!(lemmatizer.lemma(b"better", &Tag::ADJ), &b"good"[..]);
+        // the tag decides the rules
+        assert_eq!(lemmatizer.lemma(b"meeting", &Tag::N), &b"meeting"[..]);
+        assert_eq!(lemmatizer.lemma(b"meeting", &Tag::VB), &b"meet"[..]);
+        assert_eq!(lemmatizer.stem(b"went"), &b"go"[..]);
+        assert_eq!(lemmatizer.stem(b"cats"), &b"cat"[..]);
+
+        let lemmatizer = Lemmatizer::new().words(&Tag::VB, &["hope", "wash", "boss"]);
+
+        assert_eq!(lemmatizer.lemma(b"hoped", &Tag::VB), &b"hope"[..]);
+        assert_eq!(lemmatizer.lemma(b"hopes", &Tag::VB), &b"hope"[..]);
+        assert_eq!(lemmatizer.lemma(b"washes", &Tag::VB), &b"wash"[..]);
+        assert_eq!(lemmatizer.lemma(b"boss", &Tag::VB), &b"boss"[..]);
+        assert_eq!(lemmatizer.lemma(b"walked", &Tag::VB), &b"walked"[..]);
+    }
+
+    #[test]
+    fn no_made_up_words() {
+        let lemmatizer = Lemmatizer::new();
+        let lemmas = [
+            ("glass", "glass"), ("class", "class"), ("business", "business"), ("horses", "horse"),
+            ("series", "series"), ("specimen", "specimen"), ("this", "this"), ("us", "us"),
+            ("glasses", "glass"), ("buses", "bus"), ("sizes", "size"), ("gas", "gas"), ("bias", "bias"),
+            ("lenses", "lens"),
+        ];
+
+        for &(word, expected) in &lemmas {
+            assert_eq!(lemmatizer.lemma(word.as_bytes(), &Tag::N), expected.as_bytes(), "{}", word);
+            assert_eq!(lemmatizer.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+
+        let lemmas = [
+            (Tag::VB, "washes", "wash"), (Tag::VB, "watches", "watch"), (Tag::VB, "fixes", "fix"),
+            (Tag::VB, "uses", "use"), (Tag::VB, "making", "make"), (Tag::VB, "hoped", "hope"),
+            (Tag::VB, "hopped", "hop"), (Tag::VB, "sing", "sing"), (Tag::ADJ, "nicer", "nice"),
+            (Tag::ADJ, "larger", "large"), (Tag::ADJ, "bigger", "big"), (Tag::ADJ, "taller", "tall"),
+        ];
+
+        for &(ref tag, word, expected) in &lemmas {
+            assert_eq!(lemmatizer.lemma(word.as_bytes(), tag), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn wordnet_files() {
+        let lemmatizer = Lemmatizer::wordnet("./test_assets/wordnet/").unwrap();
+
+        assert_eq!(lemmatizer.lemma(b"oxen", &Tag::N), &b"ox"[..]);
+        assert_eq!(lemmatizer.lemma(b"mice", &Tag::N), &b"mouse"[..]);
+        assert_eq!(lemmatizer.lemma(b"glasses", &Tag::N), &b"glass"[..]);
+        assert_eq!(lemmatizer.lemma(b"ran", &Tag::VB), &b"run"[..]);
+        // `went` is only an exception of the built-in table
+        assert_eq!(lemmatizer.lemma(b"went", &Tag::VB), &b"went"[..]);
+    }
+
+    #[test]
+    fn index_lemmas() {
+        let mut tags = HashMap::new();
+        tags.insert(b"ed".to_vec(), Tag::VB);
+        let tagger = PosTagger::new(tags);
+        let lemmas = Lemmatizer::new().lemmatize(&["the", "mice", "were", "walked", "cats"], &tagger);
+
+        assert_eq!(lemmas, vec![b"the".to_vec(), b"mouse".to_vec(), b"be".to_vec(), b"walk".to_vec(), b"cat".to_vec()]);
+
+        let mut index = InvertedIndex::new();
+        index.add_doc(&lemmas, 0);
+
+        assert_eq!(index.get_docs(b"mouse"), &vec![0]);
+    }
+}
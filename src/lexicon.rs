@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct TermId(pub u32);
+
+#[derive(Debug, Default)]
+pub struct Lexicon {
+    ids: HashMap<Vec<u8>,TermId>,
+    terms: Vec<Vec<u8>>,
+}
+
+impl Lexicon {
+    pub fn new() -> Self {
+        Lexicon {
+            ids: HashMap::new(),
+            terms: Vec::new(),
+        }
+    }
+    pub fn intern(&mut self, term: &[u8]) -> TermId {
+        if let Some(id) = self.ids.get(term) {
+            return *id;
+        }
+        let id = TermId(self.terms.len() as u32);
+        self.ids.insert(term.to_vec(), id);
+        self.terms.push(term.to_vec());
+        id
+    }
+    pub fn get(&self, term: &[u8]) -> Option<TermId> {
+        self.ids.get(term).cloned()
+    }
+    pub fn term(&self, id: TermId) -> &[u8] {
+        &self.terms[id.0 as usize]
+    }
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+    pub fn terms(&self) -> &[Vec<u8>] {
+        &self.terms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn intern_is_stable() {
+        let mut lexicon = Lexicon::new();
+
+        let a = lexicon.intern(b"jury");
+        let b = lexicon.intern(b"grand");
+
+        assert_eq!(lexicon.intern(b"jury"), a);
+        assert!(a != b);
+        assert_eq!(lexicon.term(b), b"grand");
+        assert_eq!(lexicon.get(b"county"), None);
+        assert_eq!(lexicon.len(), 2);
+    }
+}
@@ -1,9 +1,33 @@
 #![allow(dead_code)]
 
+extern crate memmap2;
+extern crate unicode_general_category;
+extern crate unicode_script;
+
 pub mod utils;
 pub mod scanner;
 pub mod tokenizer;
+pub mod charclass;
+pub mod dfa;
+pub mod spec;
+pub mod specfile;
+pub mod graph;
+pub mod lexicon;
+pub mod scoring;
+pub mod proximity;
+pub mod segment;
+pub mod postings;
+pub mod query;
+pub mod topk;
+pub mod document;
+pub mod highlight;
 pub mod invertedindex;
 pub mod stemmer;
+pub mod porter;
+pub mod porter2;
+pub mod lovins;
+pub mod lemmatizer;
 pub mod postagger;
-pub mod corpus;
\ No newline at end of file
+pub mod corpus;
+pub mod brown;
+pub mod sentence;
@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+
+use stemmer::*;
+
+// The Lovins stemmer (Lovins, 1968, "Development of a stemming
+// algorithm"). It takes off the longest of its 294 endings whose condition
+// on the stem holds, in a single pass, then undoubles the stem's last
+// consonant and respells its end, so that `absorption` and `absorbing`
+// both give `absorb`. Stems are always at least two letters long.
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Lovins;
+
+// Endings, longest first, with the letter of the condition the stem left
+// must meet.
+const ENDINGS: &[(&str, &str)] = &[
+    ("alistically", "B"), ("arizability", "A"), ("izationally", "B"),
+    ("antialness", "A"), ("arisations", "A"), ("arizations", "A"), ("entialness", "A"),
+    ("allically", "C"), ("antaneous", "A"), ("antiality", "A"), ("arisation", "A"), ("arization", "A"),
+    ("ationally", "B"), ("ativeness", "A"), ("eableness", "E"), ("entations", "A"), ("entiality", "A"),
+    ("entialize", "A"), ("entiation", "A"), ("ionalness", "A"), ("istically", "A"), ("itousness", "A"),
+    ("izability", "A"), ("izational", "A"),
+    ("ableness", "A"), ("arizable", "A"), ("entation", "A"), ("entially", "A"), ("eousness", "A"),
+    ("ibleness", "A"), ("icalness", "A"), ("ionalism", "A"), ("ionality", "A"), ("ionalize", "A"),
+    ("iousness", "A"), ("izations", "A"), ("lessness", "A"),
+    ("ability", "A"), ("aically", "A"), ("alistic", "B"), ("alities", "A"), ("ariness", "E"),
+    ("aristic", "A"), ("arizing", "A"), ("ateness", "A"), ("atingly", "A"), ("ational", "B"),
+    ("atively", "A"), ("ativism", "A"), ("elihood", "E"), ("encible", "A"), ("entally", "A"),
+    ("entials", "A"), ("entiate", "A"), ("entness", "A"), ("fulness", "A"), ("ibility", "A"),
+    ("icalism", "A"), ("icalist", "A"), ("icality", "A"), ("icalize", "A"), ("ication", "G"),
+    ("icianry", "A"), ("ination", "A"), ("ingness", "A"), ("ionally", "A"), ("isation", "A"),
+    ("ishness", "A"), ("istical", "A"), ("iteness", "A"), ("iveness", "A"), ("ivistic", "A"),
+    ("ivities", "A"), ("ization", "F"), ("izement", "A"), ("oidally", "A"), ("ousness", "A"),
+    ("aceous", "A"), ("acious", "B"), ("action", "G"), ("alness", "A"), ("ancial", "A"),
+    ("ancies", "A"), ("ancing", "B"), ("ariser", "A"), ("arized", "A"), ("arizer", "A"),
+    ("atable", "A"), ("ations", "B"), ("atives", "A"), ("eature", "Z"), ("efully", "A"),
+    ("encies", "A"), ("encing", "A"), ("ential", "A"), ("enting", "C"), ("entist", "A"),
+    ("eously", "A"), ("ialist", "A"), ("iality", "A"), ("ialize", "A"), ("ically", "A"),
+    ("icance", "A"), ("icians", "A"), ("icists", "A"), ("ifully", "A"), ("ionals", "A"),
+    ("ionate", "D"), ("ioning", "A"), ("ionist", "A"), ("iously", "A"), ("istics", "A"),
+    ("izable", "E"), ("lessly", "A"), ("nesses", "A"), ("oidism", "A"),
+    ("acies", "A"), ("acity", "A"), ("aging", "B"), ("aical", "A"), ("alist", "A"),
+    ("alism", "B"), ("ality", "A"), ("alize", "A"), ("allic", "BB"), ("anced", "B"),
+    ("ances", "B"), ("antic", "C"), ("arial", "A"), ("aries", "A"), ("arily", "A"),
+    ("arity", "B"), ("arize", "A"), ("aroid", "A"), ("ately", "A"), ("ating", "I"),
+    ("ation", "B"), ("ative", "A"), ("ators", "A"), ("atory", "A"), ("ature", "E"),
+    ("early", "Y"), ("ehood", "A"), ("eless", "A"), ("elity", "A"), ("ement", "A"),
+    ("enced", "A"), ("ences", "A"), ("eness", "E"), ("ening", "E"), ("ental", "A"),
+    ("ented", "C"), ("ently", "A"), ("fully", "A"), ("ially", "A"), ("icant", "A"),
+    ("ician", "A"), ("icide", "A"), ("icism", "A"), ("icist", "A"), ("icity", "A"),
+    ("idine", "I"), ("iedly", "A"), ("ihood", "A"), ("inate", "A"), ("iness", "A"),
+    ("ingly", "B"), ("inism", "J"), ("inity", "CC"), ("ional", "A"), ("ioned", "A"),
+    ("ished", "A"), ("istic", "A"), ("ities", "A"), ("itous", "A"), ("ively", "A"),
+    ("ivity", "A"), ("izers", "F"), ("izing", "F"), ("oidal", "A"), ("oides", "A"),
+    ("otide", "A"), ("ously", "A"),
+    ("able", "A"), ("ably", "A"), ("ages", "B"), ("ally", "B"), ("ance", "B"), ("ancy", "B"),
+    ("ants", "B"), ("aric", "A"), ("arly", "K"), ("ated", "I"), ("ates", "A"), ("atic", "B"),
+    ("ator", "A"), ("ealy", "Y"), ("edly", "E"), ("eful", "A"), ("eity", "A"), ("ence", "A"),
+    ("ency", "A"), ("ened", "E"), ("enly", "E"), ("eous", "A"), ("hood", "A"), ("ials", "A"),
+    ("ians", "A"), ("ible", "A"), ("ibly", "A"), ("ical", "A"), ("ides", "L"), ("iers", "A"),
+    ("iful", "A"), ("ines", "M"), ("ings", "N"), ("ions", "B"), ("ious", "A"), ("isms", "B"),
+    ("ists", "A"), ("itic", "H"), ("ized", "F"), ("izer", "F"), ("less", "A"), ("lily", "A"),
+    ("ness", "A"), ("ogen", "A"), ("ward", "A"), ("wise", "A"), ("ying", "B"), ("yish", "A"),
+    ("acy", "A"), ("age", "B"), ("aic", "A"), ("als", "BB"), ("ant", "B"), ("ars", "O"),
+    ("ary", "F"), ("ata", "A"), ("ate", "A"), ("eal", "Y"), ("ear", "Y"), ("ely", "E"),
+    ("ene", "E"), ("ent", "C"), ("ery", "E"), ("ese", "A"), ("ful", "A"), ("ial", "A"),
+    ("ian", "A"), ("ics", "A"), ("ide", "L"), ("ied", "A"), ("ier", "A"), ("ies", "P"),
+    ("ily", "A"), ("ine", "M"), ("ing", "N"), ("ion", "Q"), ("ish", "C"), ("ism", "B"),
+    ("ist", "A"), ("ite", "AA"), ("ity", "A"), ("ium", "A"), ("ive", "A"), ("ize", "F"),
+    ("oid", "A"), ("one", "R"), ("ous", "A"),
+    ("ae", "A"), ("al", "BB"), ("ar", "X"), ("as", "B"), ("ed", "E"), ("en", "F"), ("es", "E"),
+    ("ia", "A"), ("ic", "A"), ("is", "A"), ("ly", "B"), ("on", "S"), ("or", "T"), ("um", "U"),
+    ("us", "V"), ("yl", "R"), ("'s", "A"), ("s'", "A"),
+    ("a", "A"), ("e", "A"), ("i", "A"), ("o", "A"), ("s", "W"), ("y", "B"),
+];
+
+// Respellings of the stem's end, with the letters it must not follow.
+const RESPELLINGS: &[(&str, &str, &str)] = &[
+    ("iev", "ief", ""), ("uct", "uc", ""), ("umpt", "um", ""), ("rpt", "rb", ""), ("urs", "ur", ""),
+    ("istr", "ister", ""), ("metr", "meter", ""), ("olv", "olut", ""), ("ul", "l", "aoi"),
+    ("bex", "bic", ""), ("dex", "dic", ""), ("pex", "pic", ""), ("tex", "tic", ""), ("ax", "ac", ""),
+    ("ex", "ec", ""), ("ix", "ic", ""), ("lux", "luc", ""), ("uad", "uas", ""), ("vad", "vas", ""),
+    ("cid", "cis", ""), ("lid", "lis", ""), ("erid", "eris", ""), ("pand", "pans", ""),
+    ("end", "ens", "s"), ("ond", "ons", ""), ("lud", "lus", ""), ("rud", "rus", ""),
+    ("her", "hes", "pt"), ("mit", "mis", ""), ("ent", "ens", "m"), ("ert", "ers", ""),
+    ("et", "es", "n"), ("yt", "ys", ""), ("yz", "ys", ""),
+];
+
+impl Stem for Lovins {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]> {
+        let ending = ENDINGS.iter()
+            .filter(|&&(e, _)| word.ends_with(e.as_bytes()))
+            .find(|&&(e, condition)| holds(condition, &word[..word.len() - e.len()]));
+        let mut stem = match ending {
+            Some(&(e, _)) => word[..word.len() - e.len()].to_vec(),
+            None => word.to_vec()
+        };
+        let n = stem.len();
+        if n >= 2 && stem[n - 1] == stem[n - 2] && b"bdglmnprst".contains(&stem[n - 1]) {
+            stem.pop();
+        }
+        let respelling = RESPELLINGS.iter()
+            .filter(|&&(from, _, _)| stem.ends_with(from.as_bytes()))
+            .max_by_key(|&&(from, _, _)| from.len());
+        if let Some(&(from, to, unless)) = respelling {
+            let at = stem.len() - from.len();
+            if at == 0 || !unless.as_bytes().contains(&stem[at - 1]) {
+                stem.truncate(at);
+                stem.extend_from_slice(to.as_bytes());
+            }
+        }
+        stem_of(word, stem)
+    }
+}
+
+// Whether the stem left by taking off an ending meets its condition.
+fn holds(condition: &str, stem: &[u8]) -> bool {
+    let n = stem.len();
+    if n < 2 {
+        return false;
+    }
+    let ends = |s: &str| stem.ends_with(s.as_bytes());
+    let last = stem[n - 1];
+    match condition {
+        "A" => true,
+        "B" => n >= 3,
+        "C" => n >= 4,
+        "D" => n >= 5,
+        "E" => last != b'e',
+        "F" => n >= 3 && last != b'e',
+        "G" => n >= 3 && last == b'f',
+        "H" => last == b't' || ends("ll"),
+        "I" => last != b'o' && last != b'e',
+        "J" => last != b'a' && last != b'e',
+        "K" => n >= 3 && (last == b'l' || last == b'i' || last == b'e' && stem[n - 3] == b'u'),
+        "L" => last != b'u' && last != b'x' && (last != b's' || ends("os")),
+        "M" => !b"acem".contains(&last),
+        "N" => if n >= 3 && stem[n - 3] == b's' { n >= 4 } else { n >= 3 },
+        "O" => last == b'l' || last == b'i',
+        "P" => last != b'c',
+        "Q" => n >= 3 && last != b'l' && last != b'n',
+        "R" => last == b'n' || last == b'r',
+        "S" => ends("dr") || last == b't' && !ends("tt"),
+        "T" => last == b's' || last == b't' && !ends("ot"),
+        "U" => b"lmnr".contains(&last),
+        "V" => last == b'c',
+        "W" => last != b's' && last != b'u',
+        "X" => last == b'l' || last == b'i' || n >= 3 && last == b'e' && stem[n - 3] == b'u',
+        "Y" => ends("in"),
+        "Z" => last != b'f',
+        "AA" => ["d", "f", "ph", "th", "l", "er", "or", "es", "t"].iter().any(|s| ends(s)),
+        "BB" => n >= 3 && !ends("met") && !ends("ryst"),
+        "CC" => last == b'l',
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn lovins_stems() {
+        let stems = [
+            ("nationally", "nat"), ("sitting", "sit"), ("absorption", "absorb"), ("absorbing", "absorb"),
+            ("dissolve", "dissolut"), ("dissolution", "dissolut"), ("induction", "induc"),
+            ("inductive", "induc"), ("matrix", "matric"), ("matrices", "matric"),
+        ];
+
+        for &(word, expected) in &stems {
+            assert_eq!(Lovins.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn ending_conditions() {
+        // `ion` is left after an n, and stems keep two letters
+        assert_eq!(Lovins.stem(b"onion"), &b"onion"[..]);
+        assert_eq!(Lovins.stem(b"as"), &b"as"[..]);
+        assert!(matches!(Lovins.stem(b"rivers"), Cow::Borrowed(b"river")));
+    }
+}
@@ -0,0 +1,275 @@
+use std::borrow::Cow;
+
+use stemmer::*;
+
+// The Porter stemmer (Porter, 1980, "An algorithm for suffix stripping"),
+// as in Martin Porter's reference implementation, which departs from the
+// paper in two rules of step 2: `bli` becomes `ble` instead of `abli`
+// becoming `able`, and `logi` becomes `log`.
+//
+// A word is a sequence of consonant and vowel runs, [C](VC){m}[V], and
+// most rules only apply while the stem left would have a measure `m`
+// above some bound.
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Porter;
+
+impl Stem for Porter {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]> {
+        if word.len() <= 2 {
+            return Cow::Borrowed(word);
+        }
+        let mut w = Word { b: word.to_vec(), j: 0 };
+        w.step1ab();
+        if w.b.len() > 1 {
+            w.step1c();
+            w.step2();
+            w.step3();
+            w.step4();
+            w.step5();
+        }
+        stem_of(word, w.b)
+    }
+}
+
+// The word being stemmed, and where the stem before the suffix last
+// matched by `ends` stops: `b[..=j]`, with `j` -1 for an empty stem.
+struct Word {
+    b: Vec<u8>,
+    j: isize,
+}
+
+impl Word {
+    fn k(&self) -> isize {
+        self.b.len() as isize - 1
+    }
+    fn cons(&self, i: isize) -> bool {
+        match self.b[i as usize] {
+            b'a' | b'e' | b'i' | b'o' | b'u' => false,
+            b'y' => i == 0 || !self.cons(i - 1),
+            _ => true
+        }
+    }
+    // The measure of `b[..=j]`.
+    fn m(&self) -> usize {
+        let mut n = 0;
+        let mut i = 0;
+        loop {
+            if i > self.j {
+                return n;
+            }
+            if !self.cons(i) {
+                break;
+            }
+            i += 1;
+        }
+        i += 1;
+        loop {
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if self.cons(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+            n += 1;
+            loop {
+                if i > self.j {
+                    return n;
+                }
+                if !self.cons(i) {
+                    break;
+                }
+                i += 1;
+            }
+            i += 1;
+        }
+    }
+    fn vowel_in_stem(&self) -> bool {
+        (0..=self.j).any(|i| !self.cons(i))
+    }
+    fn double_cons(&self, j: isize) -> bool {
+        j >= 1 && self.b[j as usize] == self.b[j as usize - 1] && self.cons(j)
+    }
+    // Whether `b[i-2..=i]` is consonant, vowel, consonant, and the last
+    // not w, x or y, as in `hop`: where a removed `e` is restored.
+    fn cvc(&self, i: isize) -> bool {
+        if i < 2 || !self.cons(i) || self.cons(i - 1) || !self.cons(i - 2) {
+            return false;
+        }
+        !matches!(self.b[i as usize], b'w' | b'x' | b'y')
+    }
+    fn ends(&mut self, s: &[u8]) -> bool {
+        if !self.b.ends_with(s) {
+            return false;
+        }
+        self.j = self.k() - s.len() as isize;
+        true
+    }
+    fn set_to(&mut self, s: &[u8]) {
+        self.b.truncate((self.j + 1) as usize);
+        self.b.extend_from_slice(s);
+    }
+    fn replace(&mut self, s: &[u8]) {
+        if self.m() > 0 {
+            self.set_to(s);
+        }
+    }
+    // The first suffix of the list the word ends with, with what it goes
+    // to. Lists are in the reference implementation's order, where a
+    // suffix comes before the shorter ones it ends with.
+    fn suffix(&mut self, list: &[(&'static str, &'static str)]) -> Option<&'static str> {
+        list.iter().find(|&&(s, _)| self.ends(s.as_bytes())).map(|&(_, to)| to)
+    }
+    // Plurals, and -ed or -ing.
+    fn step1ab(&mut self) {
+        if self.b.last() == Some(&b's') {
+            if self.ends(b"sses") {
+                self.b.truncate(self.b.len() - 2);
+            } else if self.ends(b"ies") {
+                self.set_to(b"i");
+            } else if self.b[self.b.len() - 2] != b's' {
+                self.b.pop();
+            }
+        }
+        if self.ends(b"eed") {
+            if self.m() > 0 {
+                self.b.pop();
+            }
+        } else if (self.ends(b"ed") || self.ends(b"ing")) && self.vowel_in_stem() {
+            self.b.truncate((self.j + 1) as usize);
+            if self.ends(b"at") {
+                self.set_to(b"ate");
+            } else if self.ends(b"bl") {
+                self.set_to(b"ble");
+            } else if self.ends(b"iz") {
+                self.set_to(b"ize");
+            } else if self.double_cons(self.k()) {
+                if !matches!(self.b[self.b.len() - 1], b'l' | b's' | b'z') {
+                    self.b.pop();
+                }
+            } else if self.m() == 1 && self.cvc(self.k()) {
+                self.set_to(b"e");
+            }
+        }
+    }
+    // A final y after a vowel in the stem becomes i.
+    fn step1c(&mut self) {
+        if self.ends(b"y") && self.vowel_in_stem() {
+            let k = self.b.len() - 1;
+            self.b[k] = b'i';
+        }
+    }
+    // Double suffixes to single ones.
+    fn step2(&mut self) {
+        let to = self.suffix(&[
+            ("ational", "ate"), ("tional", "tion"), ("enci", "ence"), ("anci", "ance"), ("izer", "ize"),
+            ("bli", "ble"), ("alli", "al"), ("entli", "ent"), ("eli", "e"), ("ousli", "ous"),
+            ("ization", "ize"), ("ation", "ate"), ("ator", "ate"), ("alism", "al"), ("iveness", "ive"),
+            ("fulness", "ful"), ("ousness", "ous"), ("aliti", "al"), ("iviti", "ive"), ("biliti", "ble"),
+            ("logi", "log"),
+        ]);
+        if let Some(to) = to {
+            self.replace(to.as_bytes());
+        }
+    }
+    fn step3(&mut self) {
+        let to = self.suffix(&[
+            ("icate", "ic"), ("ative", ""), ("alize", "al"), ("iciti", "ic"), ("ical", "ic"), ("ful", ""),
+            ("ness", ""),
+        ]);
+        if let Some(to) = to {
+            self.replace(to.as_bytes());
+        }
+    }
+    // Suffixes taken off stems of measure above 1.
+    fn step4(&mut self) {
+        const SUFFIXES: &[&str] = &[
+            "al", "ance", "ence", "er", "ic", "able", "ible", "ant", "ement", "ment", "ent", "ion", "ou",
+            "ism", "ate", "iti", "ous", "ive", "ize",
+        ];
+        let found = match SUFFIXES.iter().find(|s| self.ends(s.as_bytes())) {
+            Some(&"ion") => self.j >= 0 && matches!(self.b[self.j as usize], b's' | b't'),
+            Some(_) => true,
+            None => false
+        };
+        if found && self.m() > 1 {
+            self.b.truncate((self.j + 1) as usize);
+        }
+    }
+    // A final e, and a double l, off longer stems.
+    fn step5(&mut self) {
+        self.j = self.k();
+        if self.b.last() == Some(&b'e') {
+            let m = self.m();
+            if m > 1 || m == 1 && !self.cvc(self.k() - 1) {
+                self.b.pop();
+                self.j -= 1;
+            }
+        }
+        if self.b.last() == Some(&b'l') && self.double_cons(self.k()) && self.m() > 1 {
+            self.b.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn published_vocabulary() {
+        // words from the start of voc.txt, the vocabulary published with
+        // the reference implementation, and their stems from output.txt
+        let voc = fs::read_to_string("./test_assets/stemmers/porter_voc.txt").unwrap();
+        let output = fs::read_to_string("./test_assets/stemmers/porter_output.txt").unwrap();
+
+        for (word, expected) in voc.lines().zip(output.lines()) {
+            assert_eq!(Porter.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn paper_examples() {
+        // the examples given for each step in Porter's paper
+        let stems = [
+            ("caresses", "caress"), ("ponies", "poni"), ("ties", "ti"), ("caress", "caress"), ("cats", "cat"),
+            ("feed", "feed"), ("agreed", "agre"), ("plastered", "plaster"), ("bled", "bled"),
+            ("motoring", "motor"), ("sing", "sing"), ("conflated", "conflat"), ("troubled", "troubl"),
+            ("sized", "size"), ("hopping", "hop"), ("tanned", "tan"), ("falling", "fall"), ("hissing", "hiss"),
+            ("fizzed", "fizz"), ("failing", "fail"), ("filing", "file"), ("happy", "happi"), ("sky", "sky"),
+            ("relational", "relat"), ("conditional", "condit"), ("rational", "ration"), ("valenci", "valenc"),
+            ("hesitanci", "hesit"), ("digitizer", "digit"), ("conformabli", "conform"), ("radicalli", "radic"),
+            ("differentli", "differ"), ("vileli", "vile"), ("analogousli", "analog"),
+            ("vietnamization", "vietnam"), ("predication", "predic"), ("operator", "oper"),
+            ("feudalism", "feudal"), ("decisiveness", "decis"), ("hopefulness", "hope"),
+            ("callousness", "callous"), ("formaliti", "formal"), ("sensitiviti", "sensit"),
+            ("sensibiliti", "sensibl"), ("triplicate", "triplic"), ("formative", "form"),
+            ("formalize", "formal"), ("electriciti", "electr"), ("electrical", "electr"), ("hopeful", "hope"),
+            ("goodness", "good"), ("revival", "reviv"), ("allowance", "allow"), ("inference", "infer"),
+            ("airliner", "airlin"), ("gyroscopic", "gyroscop"), ("adjustable", "adjust"),
+            ("defensible", "defens"), ("irritant", "irrit"), ("replacement", "replac"),
+            ("adjustment", "adjust"), ("dependent", "depend"), ("adoption", "adopt"), ("homologou", "homolog"),
+            ("communism", "commun"), ("activate", "activ"), ("angulariti", "angular"),
+            ("homologous", "homolog"), ("effective", "effect"), ("bowdlerize", "bowdler"),
+            ("probate", "probat"), ("rate", "rate"), ("cease", "ceas"), ("controll", "control"),
+            ("roll", "roll"), ("generalizations", "gener"), ("oscillators", "oscil"),
+        ];
+
+        for &(word, expected) in &stems {
+            assert_eq!(Porter.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn stems_borrow_from_words() {
+        assert!(matches!(Porter.stem(b"connections"), Cow::Borrowed(b"connect")));
+        assert_eq!(Porter.stem(b"happy"), &b"happi"[..]);
+        assert_eq!(Porter.stem(b"sing"), &b"sing"[..]);
+    }
+}
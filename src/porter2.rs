@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+
+use stemmer::*;
+
+// The Porter2 stemmer, Snowball's English stemmer, which revises the
+// Porter stemmer's rules and adds some: -ly and -ingly endings, 's
+// possessives and a few irregular forms.
+//
+// Rules apply to suffixes in regions of the word: R1 starts after the
+// first consonant that follows a vowel, and R2 after the first such
+// consonant in R1. A y starting the word or following a vowel counts as
+// a consonant, and is marked as `Y` while stemming.
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Porter2;
+
+// Whole words stemmed, or left, as they are listed.
+const EXCEPTIONS: &[(&str, &str)] = &[
+    ("skis", "ski"), ("skies", "sky"), ("dying", "die"), ("lying", "lie"), ("tying", "tie"),
+    ("idly", "idl"), ("gently", "gentl"), ("ugly", "ugli"), ("early", "earli"), ("only", "onli"),
+    ("singly", "singl"), ("sky", "sky"), ("news", "news"), ("howe", "howe"), ("atlas", "atlas"),
+    ("cosmos", "cosmos"), ("bias", "bias"), ("andes", "andes"),
+];
+
+// Words left as they are once their plural is taken off.
+const INVARIANT: &[&str] = &["inning", "outing", "canning", "herring", "earring", "proceed", "exceed", "succeed"];
+
+impl Stem for Porter2 {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Some(&(_, to)) = EXCEPTIONS.iter().find(|e| e.0.as_bytes() == word) {
+            return stem_of(word, to.as_bytes().to_vec());
+        }
+        if word.len() <= 2 {
+            return Cow::Borrowed(word);
+        }
+        let mut w = word.strip_prefix(b"'").unwrap_or(word).to_vec();
+        for i in 0..w.len() {
+            if w[i] == b'y' && (i == 0 || is_vowel(w[i - 1])) {
+                w[i] = b'Y';
+            }
+        }
+        let p1 = match ["gener", "commun", "arsen"].iter().find(|p| w.starts_with(p.as_bytes())) {
+            Some(p) => p.len(),
+            None => region(&w, 0)
+        };
+        let mut w = Word { p2: region(&w, p1), b: w, p1 };
+        w.step1a();
+        if !INVARIANT.iter().any(|i| i.as_bytes() == &w.b[..]) {
+            w.step1b();
+            w.step1c();
+            w.step2();
+            w.step3();
+            w.step4();
+            w.step5();
+        }
+        for b in &mut w.b {
+            if *b == b'Y' {
+                *b = b'y';
+            }
+        }
+        stem_of(word, w.b)
+    }
+}
+
+fn is_vowel(b: u8) -> bool {
+    matches!(b, b'a' | b'e' | b'i' | b'o' | b'u' | b'y')
+}
+
+// Where the region after the first vowel and consonant from `start` begins.
+fn region(w: &[u8], start: usize) -> usize {
+    let vowel = match (start..w.len()).find(|&i| is_vowel(w[i])) {
+        Some(i) => i,
+        None => return w.len()
+    };
+    (vowel + 1..w.len()).find(|&i| !is_vowel(w[i])).map_or(w.len(), |i| i + 1)
+}
+
+struct Word {
+    b: Vec<u8>,
+    p1: usize,
+    p2: usize,
+}
+
+impl Word {
+    // The longest suffix of the list the word ends with, with where it
+    // starts and what goes with it.
+    fn suffix<T: Copy>(&self, list: &[(&str, T)]) -> Option<(usize, T)> {
+        list.iter()
+            .filter(|&&(s, _)| self.b.ends_with(s.as_bytes()))
+            .max_by_key(|&&(s, _)| s.len())
+            .map(|&(s, t)| (self.b.len() - s.len(), t))
+    }
+    fn replace(&mut self, at: usize, to: &str) {
+        self.b.truncate(at);
+        self.b.extend_from_slice(to.as_bytes());
+    }
+    fn ends_with(&self, at: usize, s: &[u8]) -> bool {
+        self.b[..at].ends_with(s)
+    }
+    // Whether `b[..at]` ends in a short syllable: a consonant, a vowel and
+    // a consonant other than w, x or Y, or a vowel and a consonant that
+    // start the word.
+    fn short_syllable(&self, at: usize) -> bool {
+        let b = &self.b[..at];
+        match at {
+            2 => is_vowel(b[0]) && !is_vowel(b[1]),
+            n if n >= 3 => !is_vowel(b[n - 3]) && is_vowel(b[n - 2]) && !is_vowel(b[n - 1]) && !matches!(b[n - 1], b'w' | b'x' | b'Y'),
+            _ => false
+        }
+    }
+    // Possessives and plurals.
+    fn step1a(&mut self) {
+        if let Some((at, ())) = self.suffix(&[("'", ()), ("'s", ()), ("'s'", ())]) {
+            self.b.truncate(at);
+        }
+        // `-` marks an s taken off after a vowel that does not come right
+        // before it, and `us` and `ss` stay
+        match self.suffix(&[("sses", "ss"), ("ied", "i"), ("ies", "i"), ("s", "-"), ("us", "us"), ("ss", "ss")]) {
+            Some((at, "i")) => {
+                let to = if at > 1 { "i" } else { "ie" };
+                self.replace(at, to);
+            },
+            Some((at, "-")) if at >= 1 && self.b[..at - 1].iter().any(|&b| is_vowel(b)) => self.b.truncate(at),
+            Some((at, to)) if to != "-" => self.replace(at, to),
+            _ => {}
+        }
+    }
+    // -eed, -ed and -ing, with their -ly forms.
+    fn step1b(&mut self) {
+        let found = self.suffix(&[
+            ("eed", true), ("eedly", true), ("ed", false), ("edly", false), ("ing", false), ("ingly", false),
+        ]);
+        match found {
+            Some((at, true)) if at >= self.p1 => self.replace(at, "ee"),
+            Some((at, false)) if self.b[..at].iter().any(|&b| is_vowel(b)) => {
+                self.b.truncate(at);
+                if ["at", "bl", "iz"].iter().any(|s| self.b.ends_with(s.as_bytes())) {
+                    self.b.push(b'e');
+                } else if ["bb", "dd", "ff", "gg", "mm", "nn", "pp", "rr", "tt"].iter().any(|s| self.b.ends_with(s.as_bytes())) {
+                    self.b.pop();
+                } else if self.p1 >= self.b.len() && self.short_syllable(self.b.len()) {
+                    self.b.push(b'e');
+                }
+            },
+            _ => {}
+        }
+    }
+    // A final y after a consonant that does not start the word becomes i.
+    fn step1c(&mut self) {
+        let n = self.b.len();
+        if n >= 3 && matches!(self.b[n - 1], b'y' | b'Y') && !is_vowel(self.b[n - 2]) {
+            self.b[n - 1] = b'i';
+        }
+    }
+    fn step2(&mut self) {
+        let found = self.suffix(&[
+            ("tional", "tion"), ("enci", "ence"), ("anci", "ance"), ("abli", "able"), ("entli", "ent"),
+            ("izer", "ize"), ("ization", "ize"), ("ational", "ate"), ("ation", "ate"), ("ator", "ate"),
+            ("alism", "al"), ("aliti", "al"), ("alli", "al"), ("fulness", "ful"), ("ousli", "ous"),
+            ("ousness", "ous"), ("iveness", "ive"), ("iviti", "ive"), ("biliti", "ble"), ("bli", "ble"),
+            ("ogi", "og"), ("fulli", "ful"), ("lessli", "less"), ("li", ""),
+        ]);
+        let (at, to) = match found {
+            Some(found) if found.0 >= self.p1 => found,
+            _ => return
+        };
+        match to {
+            "og" if !self.ends_with(at, b"l") => {},
+            "" if !at.checked_sub(1).is_some_and(|i| b"cdeghkmnrt".contains(&self.b[i])) => {},
+            _ => self.replace(at, to)
+        }
+    }
+    fn step3(&mut self) {
+        let found = self.suffix(&[
+            ("tional", "tion"), ("ational", "ate"), ("alize", "al"), ("icate", "ic"), ("iciti", "ic"),
+            ("ical", "ic"), ("ful", ""), ("ness", ""), ("ative", "R2"),
+        ]);
+        match found {
+            Some((at, "R2")) if at >= self.p2 => self.b.truncate(at),
+            Some((at, to)) if to != "R2" && at >= self.p1 => self.replace(at, to),
+            _ => {}
+        }
+    }
+    fn step4(&mut self) {
+        let found = self.suffix(&[
+            ("al", false), ("ance", false), ("ence", false), ("er", false), ("ic", false), ("able", false),
+            ("ible", false), ("ant", false), ("ement", false), ("ment", false), ("ent", false), ("ism", false),
+            ("ate", false), ("iti", false), ("ous", false), ("ive", false), ("ize", false), ("ion", true),
+        ]);
+        if let Some((at, ion)) = found {
+            if at >= self.p2 && (!ion || self.ends_with(at, b"s") || self.ends_with(at, b"t")) {
+                self.b.truncate(at);
+            }
+        }
+    }
+    fn step5(&mut self) {
+        let at = self.b.len().saturating_sub(1);
+        match self.b.last() {
+            Some(&b'e') if at >= self.p2 || at >= self.p1 && !self.short_syllable(at) => {
+                self.b.pop();
+            },
+            Some(&b'l') if at >= self.p2 && self.ends_with(at, b"l") => {
+                self.b.pop();
+            },
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn published_vocabulary() {
+        // the sample words and stems shown with the Snowball English
+        // stemmer
+        let voc = fs::read_to_string("./test_assets/stemmers/porter2_voc.txt").unwrap();
+        let output = fs::read_to_string("./test_assets/stemmers/porter2_output.txt").unwrap();
+
+        for (word, expected) in voc.lines().zip(output.lines()) {
+            assert_eq!(Porter2.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn algorithm_examples() {
+        // examples from the description of the algorithm's steps
+        let stems = [
+            ("generously", "generous"), ("communication", "communic"), ("hopping", "hop"), ("hoping", "hope"),
+            ("cried", "cri"), ("ties", "tie"), ("gas", "gas"), ("gaps", "gap"), ("kiwis", "kiwi"),
+            ("luxuriating", "luxuri"), ("fluently", "fluentli"), ("tied", "tie"),
+        ];
+
+        for &(word, expected) in &stems {
+            assert_eq!(Porter2.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn exceptions() {
+        assert_eq!(Porter2.stem(b"skies"), &b"sky"[..]);
+        assert_eq!(Porter2.stem(b"news"), &b"news"[..]);
+        assert_eq!(Porter2.stem(b"innings"), &b"inning"[..]);
+        assert_eq!(Porter2.stem(b"'tis"), &b"tis"[..]);
+    }
+}
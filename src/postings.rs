@@ -0,0 +1,119 @@
+// Set operations over postings lists sorted by doc id.
+
+// Index of the first doc id at or after `from` that is >= `target`, found by
+// doubling the step until it overshoots and then binary searching the last
+// step. Cheap when the target is close, logarithmic when it is far away.
+pub fn gallop(list: &[u32], from: usize, target: u32) -> usize {
+    if from >= list.len() || list[from] >= target {
+        return from;
+    }
+    let mut lo = from;
+    let mut step = 1;
+    while lo + step < list.len() && list[lo + step] < target {
+        lo += step;
+        step *= 2;
+    }
+    let hi = (lo + step).min(list.len());
+    lo + 1 + list[lo + 1..hi].partition_point(|&d| d < target)
+}
+
+pub fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let mut out = Vec::with_capacity(small.len());
+    let mut at = 0;
+    for &d in small {
+        at = gallop(large, at, d);
+        if at == large.len() {
+            break;
+        }
+        if large[at] == d {
+            out.push(d);
+        }
+    }
+    out
+}
+
+// Intersects the shortest lists first so the candidate set shrinks early.
+pub fn intersect_all(mut lists: Vec<Vec<u32>>) -> Vec<u32> {
+    if lists.is_empty() {
+        return Vec::new();
+    }
+    lists.sort_by_key(|l| l.len());
+    let mut lists = lists.into_iter();
+    let mut out = lists.next().unwrap();
+    for l in lists {
+        if out.is_empty() {
+            break;
+        }
+        out = intersect(&out, &l);
+    }
+    out
+}
+
+pub fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            out.push(a[i]);
+            i += 1;
+        } else if b[j] < a[i] {
+            out.push(b[j]);
+            j += 1;
+        } else {
+            out.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+pub fn union_all(lists: Vec<Vec<u32>>) -> Vec<u32> {
+    lists.into_iter().fold(Vec::new(), |acc, l| union(&acc, &l))
+}
+
+// Doc ids in `a` that are not in `b`.
+pub fn difference(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut at = 0;
+    for &d in a {
+        at = gallop(b, at, d);
+        if at == b.len() || b[at] != d {
+            out.push(d);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn gallop_finds_lower_bound() {
+        let list = vec![1, 3, 5, 7, 9, 11, 13, 15, 17];
+
+        for from in 0..list.len() {
+            for target in 0..20 {
+                let expected = from + list[from..].iter().take_while(|&&d| d < target).count();
+                assert_eq!(gallop(&list, from, target), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = vec![1, 2, 5, 8, 13, 21];
+        let b = vec![2, 3, 5, 7, 11, 13, 17, 19, 23];
+
+        assert_eq!(intersect(&a, &b), vec![2, 5, 13]);
+        assert_eq!(intersect_all(vec![a.clone(), b.clone(), vec![5, 13, 40]]), vec![5, 13]);
+        assert_eq!(union(&a, &b), vec![1, 2, 3, 5, 7, 8, 11, 13, 17, 19, 21, 23]);
+        assert_eq!(difference(&a, &b), vec![1, 8, 21]);
+        assert_eq!(difference(&b, &[]), b);
+    }
+}
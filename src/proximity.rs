@@ -0,0 +1,140 @@
+// Matching over the sorted position lists of one document, one list per
+// query term, in query order.
+
+pub fn count_phrase(positions: &[&[u32]]) -> u32 {
+    count_ordered(positions, (positions.len() as u32).saturating_sub(1))
+}
+
+// Occurrences of the terms in query order where the first and last term are
+// at most `max_span` positions apart.
+pub fn count_ordered(positions: &[&[u32]], max_span: u32) -> u32 {
+    if positions.is_empty() || positions.iter().any(|p| p.is_empty()) {
+        return 0;
+    }
+    let mut matches = 0;
+    let mut cursors = vec![0usize; positions.len()];
+    for &start in positions[0] {
+        let mut prev = start;
+        let mut matched = true;
+        for i in 1..positions.len() {
+            // each list is sorted, so the cursor only ever moves forward
+            let p = positions[i];
+            while cursors[i] < p.len() && p[cursors[i]] <= prev {
+                cursors[i] += 1;
+            }
+            if cursors[i] == p.len() || p[cursors[i]] - start > max_span {
+                matched = false;
+                break;
+            }
+            prev = p[cursors[i]];
+        }
+        if matched {
+            matches += 1;
+        }
+    }
+    matches
+}
+
+// Windows containing every term in any order where the first and last term
+// are at most `max_span` positions apart. Each window is counted at its
+// leftmost position. A term given more than once needs as many distinct
+// positions in the window.
+pub fn count_unordered(positions: &[&[u32]], max_span: u32) -> u32 {
+    if positions.is_empty() || positions.iter().any(|p| p.is_empty()) {
+        return 0;
+    }
+    // no two terms share a position, so equal lists are the same term
+    let groups: Vec<usize> = (0..positions.len())
+        .map(|t| (0..t).find(|&u| positions[u] == positions[t]).unwrap_or(t))
+        .collect();
+    let mut needed = vec![0u32; positions.len()];
+    for &g in &groups {
+        needed[g] += 1;
+    }
+    let wanted = needed.iter().filter(|&&n| n > 0).count();
+    let mut merged: Vec<(u32, usize)> = positions.iter()
+        .enumerate()
+        .filter(|&(t, _)| groups[t] == t)
+        .flat_map(|(t, p)| p.iter().map(move |&pos| (pos, t)))
+        .collect();
+    merged.sort();
+
+    let mut counts = vec![0u32; positions.len()];
+    let mut covered = 0;
+    let mut matches = 0;
+    let mut right = 0;
+    for left in 0..merged.len() {
+        while covered < wanted && right < merged.len() {
+            let t = merged[right].1;
+            counts[t] += 1;
+            if counts[t] == needed[t] {
+                covered += 1;
+            }
+            right += 1;
+        }
+        if covered < wanted {
+            break;
+        }
+        if merged[right - 1].0 - merged[left].0 <= max_span {
+            matches += 1;
+        }
+        let t = merged[left].1;
+        if counts[t] == needed[t] {
+            covered -= 1;
+        }
+        counts[t] -= 1;
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn phrase() {
+        let grand: &[u32] = &[3, 10, 20];
+        let jury: &[u32] = &[4, 12, 21];
+
+        assert_eq!(count_phrase(&[grand, jury]), 2);
+        assert_eq!(count_phrase(&[jury, grand]), 0);
+        assert_eq!(count_phrase(&[]), 0);
+    }
+
+    #[test]
+    fn ordered_window() {
+        let grand: &[u32] = &[3, 10];
+        let jury: &[u32] = &[6, 12];
+
+        assert_eq!(count_ordered(&[grand, jury], 2), 1);
+        assert_eq!(count_ordered(&[grand, jury], 3), 2);
+        assert_eq!(count_ordered(&[jury, grand], 5), 1);
+    }
+
+    #[test]
+    fn unordered_window() {
+        let grand: &[u32] = &[10];
+        let jury: &[u32] = &[8, 30];
+        let said: &[u32] = &[9];
+
+        assert_eq!(count_unordered(&[grand, jury, said], 2), 1);
+        assert_eq!(count_unordered(&[grand, jury, said], 1), 0);
+        assert_eq!(count_unordered(&[grand, jury], 20), 2);
+    }
+
+    #[test]
+    fn repeated_term() {
+        let new: &[u32] = &[4];
+        let york: &[u32] = &[5];
+
+        assert_eq!(count_unordered(&[new, new], 10), 0);
+        assert_eq!(count_unordered(&[new, york, new], 10), 0);
+
+        let new: &[u32] = &[4, 7, 20];
+
+        assert_eq!(count_unordered(&[new, new], 3), 1);
+        assert_eq!(count_unordered(&[new, york, new], 3), 1);
+        assert_eq!(count_unordered(&[new, new], 20), 2);
+    }
+}
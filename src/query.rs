@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::fmt;
+
+use document::*;
+
+// Query syntax:
+//
+//   grand jury              either term (scored by how many match)
+//   +grand jury -county     grand required, county excluded, jury optional
+//   grand AND jury          both terms
+//   grand OR jury           either term
+//   NOT county              every document without county
+//   (grand OR petit) AND jury
+//   "grand jury"            the exact phrase
+//   title:jury              jury in the title field
+//   title:(grand OR jury)   a field applies to everything it prefixes
+//
+// AND binds tighter than OR, and both bind tighter than juxtaposition.
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Query {
+    Term(Vec<u8>),
+    Phrase(Vec<Vec<u8>>),
+    Field(String, Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Bool { must: Vec<Query>, should: Vec<Query>, must_not: Vec<Query> },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct QueryError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Lexeme {
+    Open,
+    Close,
+    Plus,
+    Minus,
+    And,
+    Or,
+    Not,
+    Field(String),
+    Phrase(Vec<Vec<u8>>),
+    Word(Vec<u8>),
+}
+
+fn is_word_byte(b: u8) -> bool {
+    !(b.is_ascii_whitespace() || b == b'(' || b == b')' || b == b'"')
+}
+
+fn lex(input: &str) -> Result<Vec<(usize, Lexeme)>, QueryError> {
+    let bytes = input.as_bytes();
+    let mut lexemes = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            },
+            b'(' => {
+                lexemes.push((start, Lexeme::Open));
+                i += 1;
+            },
+            b')' => {
+                lexemes.push((start, Lexeme::Close));
+                i += 1;
+            },
+            b'"' => {
+                let end = match bytes[i + 1..].iter().position(|&b| b == b'"') {
+                    Some(n) => i + 1 + n,
+                    None => return Err(QueryError { offset: start, message: String::from("unterminated phrase") })
+                };
+                let words = bytes[i + 1..end]
+                    .split(|b| b.is_ascii_whitespace())
+                    .filter(|w| !w.is_empty())
+                    .map(|w| w.to_vec())
+                    .collect();
+                lexemes.push((start, Lexeme::Phrase(words)));
+                i = end + 1;
+            },
+            b'+' | b'-' if i + 1 < bytes.len() && !bytes[i + 1].is_ascii_whitespace() && bytes[i + 1] != b')' => {
+                lexemes.push((start, if bytes[i] == b'+' { Lexeme::Plus } else { Lexeme::Minus }));
+                i += 1;
+            },
+            _ => {
+                while i < bytes.len() && is_word_byte(bytes[i]) {
+                    // `name:` followed by more query prefixes a field
+                    if bytes[i] == b':' && i > start && i + 1 < bytes.len() && !bytes[i + 1].is_ascii_whitespace() {
+                        break;
+                    }
+                    i += 1;
+                }
+                if i < bytes.len() && bytes[i] == b':' {
+                    lexemes.push((start, Lexeme::Field(String::from(&input[start..i]))));
+                    i += 1;
+                    continue;
+                }
+                let lexeme = match &bytes[start..i] {
+                    b"AND" => Lexeme::And,
+                    b"OR" => Lexeme::Or,
+                    b"NOT" => Lexeme::Not,
+                    w => Lexeme::Word(w.to_vec())
+                };
+                lexemes.push((start, lexeme));
+            }
+        }
+    }
+    Ok(lexemes)
+}
+
+struct Parser {
+    lexemes: Vec<(usize, Lexeme)>,
+    at: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Lexeme> {
+        self.lexemes.get(self.at).map(|l| &l.1)
+    }
+    fn offset(&self) -> usize {
+        self.lexemes.get(self.at).map_or(self.len, |l| l.0)
+    }
+    fn error(&self, message: &str) -> QueryError {
+        QueryError { offset: self.offset(), message: String::from(message) }
+    }
+    fn seq(&mut self) -> Result<Query, QueryError> {
+        let mut must = Vec::new();
+        let mut should = Vec::new();
+        let mut must_not = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(&Lexeme::Close) => break,
+                Some(&Lexeme::Plus) => {
+                    self.at += 1;
+                    must.push(self.or()?);
+                },
+                Some(&Lexeme::Minus) => {
+                    self.at += 1;
+                    must_not.push(self.or()?);
+                },
+                _ => should.push(self.or()?)
+            }
+        }
+        if must.is_empty() && must_not.is_empty() && should.len() == 1 {
+            return Ok(should.pop().unwrap());
+        }
+        if must.is_empty() && must_not.is_empty() && should.is_empty() {
+            return Err(self.error("expected a term"));
+        }
+        Ok(Query::Bool { must, should, must_not })
+    }
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.and()?];
+        while self.peek() == Some(&Lexeme::Or) {
+            self.at += 1;
+            clauses.push(self.and()?);
+        }
+        Ok(if clauses.len() == 1 { clauses.pop().unwrap() } else { Query::Or(clauses) })
+    }
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut clauses = vec![self.unary()?];
+        while self.peek() == Some(&Lexeme::And) {
+            self.at += 1;
+            clauses.push(self.unary()?);
+        }
+        Ok(if clauses.len() == 1 { clauses.pop().unwrap() } else { Query::And(clauses) })
+    }
+    fn unary(&mut self) -> Result<Query, QueryError> {
+        match self.peek() {
+            Some(&Lexeme::Not) | Some(&Lexeme::Minus) => {
+                self.at += 1;
+                Ok(Query::Not(Box::new(self.unary()?)))
+            },
+            Some(&Lexeme::Plus) => {
+                self.at += 1;
+                self.unary()
+            },
+            _ => self.primary()
+        }
+    }
+    fn primary(&mut self) -> Result<Query, QueryError> {
+        let lexeme = match self.peek() {
+            Some(l) => l.clone(),
+            None => return Err(self.error("expected a term"))
+        };
+        match lexeme {
+            Lexeme::Open => {
+                self.at += 1;
+                let q = self.seq()?;
+                if self.peek() != Some(&Lexeme::Close) {
+                    return Err(self.error("expected ')'"));
+                }
+                self.at += 1;
+                Ok(q)
+            },
+            Lexeme::Phrase(mut words) => {
+                if words.is_empty() {
+                    return Err(self.error("empty phrase"));
+                }
+                self.at += 1;
+                Ok(if words.len() == 1 { Query::Term(words.pop().unwrap()) } else { Query::Phrase(words) })
+            },
+            Lexeme::Word(w) => {
+                self.at += 1;
+                Ok(Query::Term(w))
+            },
+            Lexeme::Field(name) => {
+                self.at += 1;
+                Ok(Query::Field(name, Box::new(self.primary()?)))
+            },
+            Lexeme::Close => Err(self.error("unexpected ')'")),
+            _ => Err(self.error("expected a term"))
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        let mut parser = Parser {
+            lexemes: lex(input)?,
+            at: 0,
+            len: input.len()
+        };
+        let q = parser.seq()?;
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected ')'"));
+        }
+        Ok(q)
+    }
+    // Terms a matching document is scored on, with the field they are
+    // restricted to; excluded clauses do not count.
+    pub fn scoring_terms(&self) -> Vec<(Option<String>,Vec<u8>)> {
+        let mut terms = Vec::new();
+        self.collect_terms(None, &mut terms);
+        terms
+    }
+    fn collect_terms(&self, field: Option<&str>, terms: &mut Vec<(Option<String>,Vec<u8>)>) {
+        match *self {
+            Query::Term(ref t) => terms.push((field.map(String::from), t.clone())),
+            Query::Phrase(ref p) => terms.extend(p.iter().map(|t| (field.map(String::from), t.clone()))),
+            Query::Field(ref name, ref q) => q.collect_terms(Some(name), terms),
+            Query::And(ref qs) | Query::Or(ref qs) => {
+                for q in qs {
+                    q.collect_terms(field, terms);
+                }
+            },
+            Query::Not(_) => {},
+            Query::Bool { ref must, ref should, .. } => {
+                for q in must.iter().chain(should.iter()) {
+                    q.collect_terms(field, terms);
+                }
+            }
+        }
+    }
+    // Rewrites the query's terms into terms of one field. Fields nested
+    // inside keep their own restriction.
+    pub fn in_field(&self, field: &str) -> Query {
+        let map = |qs: &[Query]| qs.iter().map(|q| q.in_field(field)).collect();
+        match *self {
+            Query::Term(ref t) => Query::Term(field_term(field, t)),
+            Query::Phrase(ref p) => Query::Phrase(p.iter().map(|t| field_term(field, t)).collect()),
+            Query::Field(ref name, ref q) => q.in_field(name),
+            Query::And(ref qs) => Query::And(map(qs)),
+            Query::Or(ref qs) => Query::Or(map(qs)),
+            Query::Not(ref q) => Query::Not(Box::new(q.in_field(field))),
+            Query::Bool { ref must, ref should, ref must_not } => Query::Bool {
+                must: map(must),
+                should: map(should),
+                must_not: map(must_not)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn term(t: &str) -> Query {
+        Query::Term(t.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn parse_operators() {
+        assert_eq!(Query::parse("jury").unwrap(), term("jury"));
+        assert_eq!(Query::parse("grand AND jury OR county").unwrap(),
+                   Query::Or(vec![Query::And(vec![term("grand"), term("jury")]), term("county")]));
+        assert_eq!(Query::parse("(grand OR petit) AND NOT jury").unwrap(),
+                   Query::And(vec![Query::Or(vec![term("grand"), term("petit")]), Query::Not(Box::new(term("jury")))]));
+    }
+
+    #[test]
+    fn parse_modifiers_and_phrases() {
+        assert_eq!(Query::parse("+\"grand jury\" said -county").unwrap(), Query::Bool {
+            must: vec![Query::Phrase(vec![b"grand".to_vec(), b"jury".to_vec()])],
+            should: vec![term("said")],
+            must_not: vec![term("county")]
+        });
+        assert_eq!(Query::parse("co-op").unwrap(), term("co-op"));
+    }
+
+    #[test]
+    fn parse_fields() {
+        assert_eq!(Query::parse("title:jury said").unwrap(), Query::Bool {
+            must: vec![],
+            should: vec![Query::Field(String::from("title"), Box::new(term("jury"))), term("said")],
+            must_not: vec![]
+        });
+        assert_eq!(Query::parse("title:(grand OR jury)").unwrap().scoring_terms(), vec![
+            (Some(String::from("title")), b"grand".to_vec()),
+            (Some(String::from("title")), b"jury".to_vec())
+        ]);
+        assert_eq!(Query::parse("title:\"grand jury\"").unwrap().in_field("body"),
+                   Query::Phrase(vec![field_term("title", b"grand"), field_term("title", b"jury")]));
+        assert_eq!(Query::parse("ratio 2:").unwrap(), Query::Bool {
+            must: vec![],
+            should: vec![term("ratio"), term("2:")],
+            must_not: vec![]
+        });
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Query::parse("(grand jury").unwrap_err().offset, 11);
+        assert_eq!(Query::parse("grand AND").unwrap_err().message, "expected a term");
+        assert_eq!(Query::parse("\"grand jury").unwrap_err().offset, 0);
+        assert_eq!(Query::parse("jury)").unwrap_err().offset, 4);
+    }
+}
@@ -1,6 +1,7 @@
 use std::io;
 use std::io::prelude::*;
 use std::fs::File;
+use std::io::BufReader;
 
 const DEFAULT_BUF_SIZE: usize = 64 * 1024;
 
@@ -19,23 +20,14 @@ impl Scanner {
         }
         pub fn scan(&self) -> io::Result<Vec<u8>> {
                 let mut contents: Vec<u8> = Vec::new();
-
-                let mut f = try!(File::open(&self.file));
-                let mut buffer = [0; DEFAULT_BUF_SIZE];
-                let mut data_size_within_buffer;
-
-                loop {
-                        data_size_within_buffer = f.read(&mut buffer).unwrap();
-                        for b in 0..data_size_within_buffer {
-                                contents.push(buffer[b]);
-                        }
-                        if data_size_within_buffer < DEFAULT_BUF_SIZE {
-                                break;
-                        }
-                }
-
+                self.reader()?.read_to_end(&mut contents)?;
                 Ok(contents)
         }
+        // Buffered reader over the file, for tokenizing it as a stream
+        // instead of reading it all into memory.
+        pub fn reader(&self) -> io::Result<BufReader<File>> {
+                Ok(BufReader::with_capacity(DEFAULT_BUF_SIZE, File::open(&self.file)?))
+        }
 }
 
 #[cfg(test)]
@@ -51,4 +43,14 @@ mod tests {
 
                 assert_eq!(contents.len(), 3);
         }
+
+        #[test]
+        fn read_test_file_as_stream() {
+                let scanner = Scanner::new("./test_assets/foo.txt");
+
+                let mut contents = Vec::new();
+                scanner.reader().unwrap().read_to_end(&mut contents).unwrap();
+
+                assert_eq!(contents, scanner.scan().unwrap());
+        }
 }
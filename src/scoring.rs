@@ -0,0 +1,307 @@
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollectionStats {
+    pub num_docs: u32,
+    pub total_len: u64,
+    pub avg_doc_len: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TermStats {
+    pub df: u32,
+    pub cf: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DocStats {
+    pub len: u32,
+    pub norm: f64,
+}
+
+// Orders scores with NaN below every other score, so sorts and heaps of
+// scores see a total order even when a scorer returns NaN.
+pub fn cmp_scores(a: f64, b: f64) -> Ordering {
+    let key = |s: f64| if s.is_nan() { f64::NEG_INFINITY } else { s };
+    key(a).total_cmp(&key(b))
+}
+
+pub trait Scorer {
+    // contribution of one query term occurring `tf` times in a document
+    fn score(&self, tf: u32, qtf: u32, doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64;
+
+    fn query_weight(&self, qtf: u32, _term: &TermStats, _coll: &CollectionStats) -> f64 {
+        qtf as f64
+    }
+
+    // applied once per document to the summed term contributions
+    fn finish(&self, score: f64, _doc: &DocStats, _query_norm: f64) -> f64 {
+        score
+    }
+
+    // Upper bound on what the term adds to a finished score, given the
+    // largest tf and the shortest document among its postings. The default
+    // holds for scorers that grow with tf, shrink with length and have no
+    // `finish` step.
+    fn max_score(&self, max_tf: u32, min_len: u32, qtf: u32, term: &TermStats, coll: &CollectionStats, _query_norm: f64) -> f64 {
+        self.score(max_tf, qtf, &DocStats { len: min_len, norm: 1.0 }, term, coll)
+    }
+}
+
+// Cosine similarity between tf document vectors and tf-idf query vectors.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TfIdf;
+
+impl TfIdf {
+    pub fn idf(term: &TermStats, coll: &CollectionStats) -> f64 {
+        1.0 + (coll.num_docs as f64 / term.df as f64).ln()
+    }
+}
+
+impl Scorer for TfIdf {
+    fn score(&self, tf: u32, qtf: u32, _doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64 {
+        tf as f64 * self.query_weight(qtf, term, coll)
+    }
+    fn query_weight(&self, qtf: u32, term: &TermStats, coll: &CollectionStats) -> f64 {
+        qtf as f64 * TfIdf::idf(term, coll)
+    }
+    fn finish(&self, score: f64, doc: &DocStats, query_norm: f64) -> f64 {
+        score / (doc.norm * query_norm)
+    }
+    // tf never exceeds the document's norm
+    fn max_score(&self, _max_tf: u32, _min_len: u32, qtf: u32, term: &TermStats, coll: &CollectionStats, query_norm: f64) -> f64 {
+        self.query_weight(qtf, term, coll) / query_norm
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Bm25 {
+    pub k1: f64,
+    pub b: f64,
+}
+
+impl Bm25 {
+    pub fn new(k1: f64, b: f64) -> Self {
+        Bm25 {
+            k1,
+            b
+        }
+    }
+    pub fn idf(term: &TermStats, coll: &CollectionStats) -> f64 {
+        let df = term.df as f64;
+        (1.0 + (coll.num_docs as f64 - df + 0.5) / (df + 0.5)).ln()
+    }
+}
+
+impl Default for Bm25 {
+    fn default() -> Self {
+        Bm25::new(1.2, 0.75)
+    }
+}
+
+impl Scorer for Bm25 {
+    fn score(&self, tf: u32, qtf: u32, doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64 {
+        let tf = tf as f64;
+        let norm = 1.0 - self.b + self.b * doc.len as f64 / coll.avg_doc_len;
+        qtf as f64 * Bm25::idf(term, coll) * tf * (self.k1 + 1.0) / (tf + self.k1 * norm)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldWeight {
+    pub name: String,
+    pub boost: f64,
+    pub b: f64,
+}
+
+// BM25F: per-field term frequencies are length-normalized and weighted,
+// then summed and saturated once, rather than scoring every field with BM25
+// and adding the results. It needs per-field postings, so it is not a
+// `Scorer`; use `InvertedIndex::get_ranking_bm25f`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bm25F {
+    pub k1: f64,
+    pub fields: Vec<FieldWeight>,
+}
+
+impl Bm25F {
+    pub fn new(k1: f64) -> Self {
+        Bm25F {
+            k1,
+            fields: Vec::new()
+        }
+    }
+    pub fn field(mut self, name: &str, boost: f64, b: f64) -> Self {
+        self.fields.push(FieldWeight {
+            name: String::from(name),
+            boost,
+            b
+        });
+        self
+    }
+    pub fn field_tf(&self, field: &FieldWeight, tf: u32, len: u32, avg_len: f64) -> f64 {
+        if avg_len == 0.0 {
+            return 0.0;
+        }
+        field.boost * tf as f64 / (1.0 - field.b + field.b * len as f64 / avg_len)
+    }
+    pub fn score(&self, weighted_tf: f64, term: &TermStats, coll: &CollectionStats) -> f64 {
+        Bm25::idf(term, coll) * weighted_tf * (self.k1 + 1.0) / (weighted_tf + self.k1)
+    }
+}
+
+impl Default for Bm25F {
+    fn default() -> Self {
+        Bm25F::new(1.2)
+    }
+}
+
+// Query likelihood with Bayesian smoothing against the collection model.
+#[derive(Debug, Clone, Copy)]
+pub struct LmDirichlet {
+    pub mu: f64,
+}
+
+impl LmDirichlet {
+    pub fn new(mu: f64) -> Self {
+        LmDirichlet {
+            mu
+        }
+    }
+}
+
+impl Default for LmDirichlet {
+    fn default() -> Self {
+        LmDirichlet::new(2000.0)
+    }
+}
+
+impl Scorer for LmDirichlet {
+    fn score(&self, tf: u32, qtf: u32, doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64 {
+        let p_coll = term.cf as f64 / coll.total_len as f64;
+        let s = (1.0 + tf as f64 / (self.mu * p_coll)).ln() + (self.mu / (doc.len as f64 + self.mu)).ln();
+        qtf as f64 * s.max(0.0)
+    }
+}
+
+// Query likelihood with linear interpolation against the collection model.
+#[derive(Debug, Clone, Copy)]
+pub struct LmJelinekMercer {
+    pub lambda: f64,
+}
+
+impl LmJelinekMercer {
+    pub fn new(lambda: f64) -> Self {
+        LmJelinekMercer {
+            lambda
+        }
+    }
+}
+
+impl Default for LmJelinekMercer {
+    fn default() -> Self {
+        LmJelinekMercer::new(0.7)
+    }
+}
+
+impl Scorer for LmJelinekMercer {
+    fn score(&self, tf: u32, qtf: u32, doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64 {
+        let p_coll = term.cf as f64 / coll.total_len as f64;
+        let p_doc = tf as f64 / doc.len as f64;
+        qtf as f64 * (1.0 + (1.0 - self.lambda) * p_doc / (self.lambda * p_coll)).ln()
+    }
+}
+
+// Divergence from randomness, InL2: inverse document frequency basic model,
+// Laplace after-effect and length normalization 2.
+#[derive(Debug, Clone, Copy)]
+pub struct Dfr {
+    pub c: f64,
+}
+
+impl Dfr {
+    pub fn new(c: f64) -> Self {
+        Dfr {
+            c
+        }
+    }
+}
+
+impl Default for Dfr {
+    fn default() -> Self {
+        Dfr::new(1.0)
+    }
+}
+
+impl Scorer for Dfr {
+    fn score(&self, tf: u32, qtf: u32, doc: &DocStats, term: &TermStats, coll: &CollectionStats) -> f64 {
+        let tfn = tf as f64 * (1.0 + self.c * coll.avg_doc_len / doc.len as f64).log2();
+        let inf = tfn * ((coll.num_docs as f64 + 1.0) / (term.df as f64 + 0.5)).log2();
+        qtf as f64 * inf / (tfn + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn coll() -> CollectionStats {
+        CollectionStats { num_docs: 100, total_len: 10000, avg_doc_len: 100.0 }
+    }
+
+    #[test]
+    fn bm25_saturates_tf() {
+        let bm25 = Bm25::default();
+        let doc = DocStats { len: 100, norm: 1.0 };
+        let term = TermStats { df: 10, cf: 50 };
+
+        let s1 = bm25.score(1, 1, &doc, &term, &coll());
+        let s2 = bm25.score(2, 1, &doc, &term, &coll());
+        let s20 = bm25.score(20, 1, &doc, &term, &coll());
+
+        assert!(s2 > s1);
+        assert!(s20 < Bm25::idf(&term, &coll()) * (bm25.k1 + 1.0));
+    }
+
+    #[test]
+    fn bm25_penalizes_long_docs() {
+        let bm25 = Bm25::default();
+        let term = TermStats { df: 10, cf: 50 };
+
+        let short = bm25.score(3, 1, &DocStats { len: 50, norm: 1.0 }, &term, &coll());
+        let long = bm25.score(3, 1, &DocStats { len: 400, norm: 1.0 }, &term, &coll());
+
+        assert!(short > long);
+    }
+
+    #[test]
+    fn bm25f_weights_fields() {
+        let bm25f = Bm25F::default().field("title", 3.0, 0.5).field("body", 1.0, 0.75);
+        let term = TermStats { df: 10, cf: 50 };
+        let in_title = bm25f.field_tf(&bm25f.fields[0], 1, 8, 8.0);
+        let in_body = bm25f.field_tf(&bm25f.fields[1], 1, 100, 100.0);
+
+        assert_eq!(in_title, 3.0 * in_body);
+        assert!(bm25f.score(in_title, &term, &coll()) > bm25f.score(in_body, &term, &coll()));
+        assert!(bm25f.score(in_title + in_body, &term, &coll()) < Bm25::idf(&term, &coll()) * (bm25f.k1 + 1.0));
+    }
+
+    #[test]
+    fn rarer_terms_score_higher() {
+        let doc = DocStats { len: 100, norm: 1.0 };
+        let rare = TermStats { df: 2, cf: 2 };
+        let common = TermStats { df: 90, cf: 900 };
+        let scorers: Vec<Box<dyn Scorer>> = vec![
+            Box::new(TfIdf),
+            Box::new(Bm25::default()),
+            Box::new(LmDirichlet::default()),
+            Box::new(LmJelinekMercer::default()),
+            Box::new(Dfr::default())
+        ];
+
+        for scorer in scorers {
+            assert!(scorer.score(2, 1, &doc, &rare, &coll()) > scorer.score(2, 1, &doc, &common, &coll()));
+        }
+    }
+}
@@ -0,0 +1,617 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+
+use scoring::*;
+use document::*;
+
+// Segment layout, all integers little-endian:
+//
+//   header     magic, version, counts, section offsets, checksums
+//   lexicon    one fixed-size entry per term, sorted by term bytes
+//   term blob  the term bytes the lexicon entries point into
+//   docs       one fixed-size entry per document, sorted by doc id
+//   fields     per field: its name and a docs-style entry per document
+//              (version 2 and later)
+//   stored     per document: its stored fields, as text or as a byte range
+//              of a source file (version 3 and later)
+//   postings   per term: varint doc deltas, tfs and position deltas
+//
+// Everything before the postings is checked when the segment is opened;
+// each postings block carries its own checksum and is only checked when it
+// is read.
+
+pub const MAGIC: &[u8; 4] = b"GRFI";
+pub const VERSION: u32 = 3;
+// oldest version this build can still read
+pub const MIN_VERSION: u32 = 1;
+
+const HEADER_LEN: usize = 56;
+const TERM_ENTRY_LEN: usize = 28;
+const DOC_ENTRY_LEN: usize = 12;
+
+type FieldTable = Vec<(String, Vec<DocEntry>)>;
+
+const STORED_TEXT: u8 = 0;
+const STORED_OFFSETS: u8 = 1;
+
+#[derive(Debug)]
+pub enum SegmentError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Checksum(&'static str),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for SegmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SegmentError::Io(ref e) => write!(f, "i/o error: {}", e),
+            SegmentError::BadMagic => write!(f, "not an index segment (bad magic bytes)"),
+            SegmentError::UnsupportedVersion(v) => write!(f, "unsupported segment version {} (expected {} to {})", v, MIN_VERSION, VERSION),
+            SegmentError::Checksum(section) => write!(f, "checksum mismatch in {}", section),
+            SegmentError::Corrupt(what) => write!(f, "corrupt segment: {}", what),
+        }
+    }
+}
+
+impl Error for SegmentError {}
+
+impl From<io::Error> for SegmentError {
+    fn from(e: io::Error) -> Self {
+        SegmentError::Io(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Posting {
+    pub doc: u32,
+    pub tf: u32,
+    pub positions: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DocEntry {
+    pub doc: u32,
+    pub len: u32,
+    pub weight: f32,
+}
+
+// `terms` must be sorted by term bytes and each postings list by doc id.
+pub fn write_segment<W: Write>(out: &mut W, terms: &[(&[u8], Vec<Posting>)], docs: &[DocEntry], fields: &[(&str, Vec<DocEntry>)], stored: &[(u32, &[(String, Stored)])], total_len: u64) -> io::Result<()> {
+    let mut lexicon: Vec<u8> = Vec::with_capacity(terms.len() * TERM_ENTRY_LEN);
+    let mut blob: Vec<u8> = Vec::new();
+    let mut postings: Vec<u8> = Vec::new();
+
+    for &(term, ref plist) in terms {
+        let start = postings.len();
+        let mut prev_doc = 0;
+        for p in plist {
+            write_varint(&mut postings, p.doc - prev_doc);
+            write_varint(&mut postings, p.tf);
+            let mut prev_pos = 0;
+            for &pos in &p.positions {
+                write_varint(&mut postings, pos - prev_pos);
+                prev_pos = pos;
+            }
+            prev_doc = p.doc;
+        }
+        put_u32(&mut lexicon, blob.len() as u32);
+        put_u32(&mut lexicon, term.len() as u32);
+        put_u32(&mut lexicon, plist.len() as u32);
+        put_u64(&mut lexicon, start as u64);
+        put_u32(&mut lexicon, (postings.len() - start) as u32);
+        put_u32(&mut lexicon, crc32(&postings[start..]));
+        blob.extend_from_slice(term);
+    }
+
+    let mut doc_section: Vec<u8> = Vec::with_capacity(docs.len() * DOC_ENTRY_LEN);
+    put_doc_entries(&mut doc_section, docs);
+    put_u32(&mut doc_section, fields.len() as u32);
+    for &(name, ref entries) in fields {
+        put_bytes(&mut doc_section, name.as_bytes());
+        put_u32(&mut doc_section, entries.len() as u32);
+        put_doc_entries(&mut doc_section, entries);
+    }
+    put_u32(&mut doc_section, stored.len() as u32);
+    for &(doc, values) in stored {
+        put_u32(&mut doc_section, doc);
+        put_u32(&mut doc_section, values.len() as u32);
+        for (name, value) in values {
+            put_bytes(&mut doc_section, name.as_bytes());
+            match *value {
+                Stored::Text(ref text) => {
+                    doc_section.push(STORED_TEXT);
+                    put_bytes(&mut doc_section, text);
+                },
+                Stored::Offsets { ref path, start, end } => {
+                    doc_section.push(STORED_OFFSETS);
+                    put_bytes(&mut doc_section, path.to_string_lossy().as_bytes());
+                    put_u64(&mut doc_section, start);
+                    put_u64(&mut doc_section, end);
+                }
+            }
+        }
+    }
+
+    let docs_off = HEADER_LEN + lexicon.len() + blob.len();
+    let postings_off = docs_off + doc_section.len();
+
+    let mut sections = lexicon;
+    sections.append(&mut blob);
+    sections.append(&mut doc_section);
+
+    let mut header: Vec<u8> = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    put_u32(&mut header, VERSION);
+    put_u32(&mut header, terms.len() as u32);
+    put_u32(&mut header, docs.len() as u32);
+    put_u64(&mut header, total_len);
+    put_u64(&mut header, HEADER_LEN as u64);
+    put_u64(&mut header, docs_off as u64);
+    put_u64(&mut header, postings_off as u64);
+    put_u32(&mut header, crc32(&sections));
+    let header_crc = crc32(&header);
+    put_u32(&mut header, header_crc);
+
+    out.write_all(&header)?;
+    out.write_all(&sections)?;
+    out.write_all(&postings)?;
+    out.flush()
+}
+
+pub struct Segment {
+    mmap: Mmap,
+    num_terms: usize,
+    num_docs: usize,
+    total_len: u64,
+    blob_off: usize,
+    docs_off: usize,
+    postings_off: usize,
+}
+
+impl Segment {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SegmentError> {
+        let file = File::open(path)?;
+        // the mapping is read-only and segments are never modified in place
+        let mmap = unsafe { Mmap::map(&file)? };
+        Segment::from_mmap(mmap)
+    }
+    fn from_mmap(mmap: Mmap) -> Result<Self, SegmentError> {
+        if mmap.len() < 8 {
+            return Err(SegmentError::Corrupt("file too short for header"));
+        }
+        if &mmap[0..4] != MAGIC {
+            return Err(SegmentError::BadMagic);
+        }
+        let version = get_u32(&mmap, 4);
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(SegmentError::UnsupportedVersion(version));
+        }
+        if mmap.len() < HEADER_LEN {
+            return Err(SegmentError::Corrupt("file too short for header"));
+        }
+        if crc32(&mmap[0..HEADER_LEN - 4]) != get_u32(&mmap, HEADER_LEN - 4) {
+            return Err(SegmentError::Checksum("header"));
+        }
+
+        let num_terms = get_u32(&mmap, 8) as usize;
+        let num_docs = get_u32(&mmap, 12) as usize;
+        let total_len = get_u64(&mmap, 16);
+        let lexicon_off = get_u64(&mmap, 24) as usize;
+        let docs_off = get_u64(&mmap, 32) as usize;
+        let postings_off = get_u64(&mmap, 40) as usize;
+        // offsets come from the file, so sums of them can overflow
+        let blob_off = lexicon_off.checked_add(num_terms * TERM_ENTRY_LEN);
+        let docs_end = docs_off.checked_add(num_docs * DOC_ENTRY_LEN);
+        let blob_off = match (blob_off, docs_end) {
+            (Some(blob_off), Some(docs_end)) if lexicon_off == HEADER_LEN && blob_off <= docs_off
+                && docs_end <= postings_off && postings_off <= mmap.len() => blob_off,
+            _ => return Err(SegmentError::Corrupt("section offsets out of range"))
+        };
+        if crc32(&mmap[HEADER_LEN..postings_off]) != get_u32(&mmap, 48) {
+            return Err(SegmentError::Checksum("lexicon or document table"));
+        }
+
+        let segment = Segment {
+            mmap,
+            num_terms,
+            num_docs,
+            total_len,
+            blob_off,
+            docs_off,
+            postings_off
+        };
+        for i in 0..num_terms {
+            let (start, len) = segment.term_range(i);
+            let (p_start, p_len) = segment.postings_range(i);
+            let term_end = segment.blob_off.checked_add(start).and_then(|e| e.checked_add(len));
+            let postings_end = segment.postings_off.checked_add(p_start).and_then(|e| e.checked_add(p_len));
+            if term_end.is_none_or(|e| e > segment.docs_off) || postings_end.is_none_or(|e| e > segment.mmap.len()) {
+                return Err(SegmentError::Corrupt("lexicon entry out of range"));
+            }
+        }
+        Ok(segment)
+    }
+    pub fn num_terms(&self) -> usize {
+        self.num_terms
+    }
+    pub fn num_docs(&self) -> usize {
+        self.num_docs
+    }
+    pub fn total_len(&self) -> u64 {
+        self.total_len
+    }
+    pub fn collection_stats(&self) -> CollectionStats {
+        CollectionStats {
+            num_docs: self.num_docs as u32,
+            total_len: self.total_len,
+            avg_doc_len: if self.num_docs == 0 { 0.0 } else { self.total_len as f64 / self.num_docs as f64 }
+        }
+    }
+    fn entry_off(&self, i: usize) -> usize {
+        HEADER_LEN + i * TERM_ENTRY_LEN
+    }
+    fn term_range(&self, i: usize) -> (usize, usize) {
+        let e = self.entry_off(i);
+        (get_u32(&self.mmap, e) as usize, get_u32(&self.mmap, e + 4) as usize)
+    }
+    fn postings_range(&self, i: usize) -> (usize, usize) {
+        let e = self.entry_off(i);
+        (get_u64(&self.mmap, e + 12) as usize, get_u32(&self.mmap, e + 20) as usize)
+    }
+    pub fn term(&self, i: usize) -> &[u8] {
+        let (start, len) = self.term_range(i);
+        &self.mmap[self.blob_off + start..self.blob_off + start + len]
+    }
+    pub fn df(&self, i: usize) -> u32 {
+        get_u32(&self.mmap, self.entry_off(i) + 8)
+    }
+    pub fn find_term(&self, token: &[u8]) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.num_terms);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.term(mid).cmp(token) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+    pub fn postings(&self, i: usize) -> Result<Vec<Posting>, SegmentError> {
+        let (start, len) = self.postings_range(i);
+        let block = &self.mmap[self.postings_off + start..self.postings_off + start + len];
+        if crc32(block) != get_u32(&self.mmap, self.entry_off(i) + 24) {
+            return Err(SegmentError::Checksum("postings"));
+        }
+        let df = self.df(i) as usize;
+        let overflow = || SegmentError::Corrupt("doc id or position out of range");
+        // each entry takes at least a byte, so a count beyond the block
+        // is damage and must not size an allocation
+        let mut postings = Vec::with_capacity(df.min(block.len()));
+        let mut at = 0;
+        let mut doc: u32 = 0;
+        for _ in 0..df {
+            doc = doc.checked_add(read_varint(block, &mut at)?).ok_or_else(overflow)?;
+            let tf = read_varint(block, &mut at)?;
+            let mut positions = Vec::with_capacity((tf as usize).min(block.len()));
+            let mut pos: u32 = 0;
+            for _ in 0..tf {
+                pos = pos.checked_add(read_varint(block, &mut at)?).ok_or_else(overflow)?;
+                positions.push(pos);
+            }
+            postings.push(Posting { doc, tf, positions });
+        }
+        Ok(postings)
+    }
+    pub fn get_postings(&self, token: &[u8]) -> Result<Vec<Posting>, SegmentError> {
+        match self.find_term(token) {
+            Some(i) => self.postings(i),
+            None => Ok(Vec::new())
+        }
+    }
+    pub fn doc_entry_at(&self, i: usize) -> Option<DocEntry> {
+        if i >= self.num_docs {
+            return None;
+        }
+        Some(get_doc_entry(&self.mmap, self.docs_off + i * DOC_ENTRY_LEN))
+    }
+    pub fn get_doc_entry(&self, doc: u32) -> Option<DocEntry> {
+        let (mut lo, mut hi) = (0, self.num_docs);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let entry = self.doc_entry_at(mid)?;
+            match entry.doc.cmp(&doc) {
+                Ordering::Less => lo = mid + 1,
+                Ordering::Greater => hi = mid,
+                Ordering::Equal => return Some(entry),
+            }
+        }
+        None
+    }
+    // Per-field document lengths and norms, in the order they were written.
+    pub fn fields(&self) -> Result<FieldTable, SegmentError> {
+        self.read_fields().map(|f| f.0)
+    }
+    // Stored fields of every document that has any, sorted by doc id.
+    pub fn stored(&self) -> Result<Vec<(u32, StoredFields)>, SegmentError> {
+        let (_, mut at) = self.read_fields()?;
+        let section = self.extra_section();
+        let mut stored = Vec::new();
+        if at == section.len() {
+            return Ok(stored);
+        }
+        let truncated = || SegmentError::Corrupt("truncated stored fields");
+        let num_docs = get_u32_checked(section, &mut at).ok_or_else(truncated)?;
+        for _ in 0..num_docs {
+            let doc = get_u32_checked(section, &mut at).ok_or_else(truncated)?;
+            let count = get_u32_checked(section, &mut at).ok_or_else(truncated)?;
+            let mut values = Vec::new();
+            for _ in 0..count {
+                let name = get_bytes_checked(section, &mut at).ok_or_else(truncated)?;
+                let name = String::from_utf8(name.to_vec()).map_err(|_| SegmentError::Corrupt("field name is not utf-8"))?;
+                let kind = *section.get(at).ok_or_else(truncated)?;
+                at += 1;
+                let value = match kind {
+                    STORED_TEXT => Stored::Text(get_bytes_checked(section, &mut at).ok_or_else(truncated)?.to_vec()),
+                    STORED_OFFSETS => {
+                        let path = get_bytes_checked(section, &mut at).ok_or_else(truncated)?;
+                        let path = PathBuf::from(String::from_utf8_lossy(path).into_owned());
+                        if at + 16 > section.len() {
+                            return Err(truncated());
+                        }
+                        let (start, end) = (get_u64(section, at), get_u64(section, at + 8));
+                        at += 16;
+                        Stored::Offsets { path, start, end }
+                    },
+                    _ => return Err(SegmentError::Corrupt("unknown stored field kind"))
+                };
+                values.push((name, value));
+            }
+            stored.push((doc, values));
+        }
+        Ok(stored)
+    }
+    // the field table and stored fields that follow the docs
+    fn extra_section(&self) -> &[u8] {
+        &self.mmap[self.docs_off + self.num_docs * DOC_ENTRY_LEN..self.postings_off]
+    }
+    // Parses the field table, returning it with the offset just past it.
+    fn read_fields(&self) -> Result<(FieldTable, usize), SegmentError> {
+        let section = self.extra_section();
+        let mut fields = Vec::new();
+        if section.is_empty() {
+            return Ok((fields, 0));
+        }
+        let truncated = || SegmentError::Corrupt("truncated field table");
+        let mut at = 0;
+        let num_fields = get_u32_checked(section, &mut at).ok_or_else(truncated)?;
+        for _ in 0..num_fields {
+            let name = get_bytes_checked(section, &mut at).ok_or_else(truncated)?;
+            let name = String::from_utf8(name.to_vec()).map_err(|_| SegmentError::Corrupt("field name is not utf-8"))?;
+            let count = get_u32_checked(section, &mut at).ok_or_else(truncated)? as usize;
+            let entries = section.get(at..at + count * DOC_ENTRY_LEN).ok_or_else(truncated)?;
+            at += count * DOC_ENTRY_LEN;
+            fields.push((name, (0..count).map(|i| get_doc_entry(entries, i * DOC_ENTRY_LEN)).collect()));
+        }
+        Ok((fields, at))
+    }
+    pub fn get_ranking_with<S: Scorer + ?Sized>(&self, query: &[Vec<u8>], scorer: &S) -> Result<HashMap<u32,f64>, SegmentError> {
+        let mut weights: HashMap<u32,f64> = HashMap::new();
+        let mut doc_stats: HashMap<u32,DocStats> = HashMap::new();
+        let mut token_freqs: HashMap<&[u8],u32> = HashMap::new();
+        let mut query_weight = 0.0;
+        let coll = self.collection_stats();
+        for token in query {
+            *token_freqs.entry(token).or_insert(0) += 1;
+        }
+        for (token, freq) in token_freqs {
+            let postings = self.get_postings(token)?;
+            if postings.is_empty() {
+                continue;
+            }
+            let term = TermStats {
+                df: postings.len() as u32,
+                cf: postings.iter().map(|p| p.tf as u64).sum()
+            };
+            query_weight += scorer.query_weight(freq, &term, &coll).powi(2);
+            for p in postings {
+                let doc = match doc_stats.get(&p.doc) {
+                    Some(d) => *d,
+                    None => {
+                        let entry = self.get_doc_entry(p.doc).ok_or(SegmentError::Corrupt("posting for unknown document"))?;
+                        let d = DocStats { len: entry.len, norm: (entry.weight as f64).sqrt() };
+                        doc_stats.insert(p.doc, d);
+                        d
+                    }
+                };
+                *weights.entry(p.doc).or_insert(0.0) += scorer.score(p.tf, freq, &doc, &term, &coll);
+            }
+        }
+        let query_norm = f64::sqrt(query_weight);
+        Ok(weights.into_iter()
+            .map(|(d, w)| (d, scorer.finish(w, &doc_stats[&d], query_norm)))
+            .collect())
+    }
+}
+
+fn put_doc_entries(buf: &mut Vec<u8>, docs: &[DocEntry]) {
+    for d in docs {
+        put_u32(buf, d.doc);
+        put_u32(buf, d.len);
+        put_u32(buf, d.weight.to_bits());
+    }
+}
+
+fn get_doc_entry(buf: &[u8], at: usize) -> DocEntry {
+    DocEntry {
+        doc: get_u32(buf, at),
+        len: get_u32(buf, at + 4),
+        weight: f32::from_bits(get_u32(buf, at + 8))
+    }
+}
+
+fn get_u32_checked(buf: &[u8], at: &mut usize) -> Option<u32> {
+    if *at + 4 > buf.len() {
+        return None;
+    }
+    let v = get_u32(buf, *at);
+    *at += 4;
+    Some(v)
+}
+
+// length-prefixed bytes
+fn get_bytes_checked<'a>(buf: &'a [u8], at: &mut usize) -> Option<&'a [u8]> {
+    let len = get_u32_checked(buf, at)? as usize;
+    let bytes = buf.get(*at..at.checked_add(len)?)?;
+    *at += len;
+    Some(bytes)
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_u32(buf, bytes.len() as u32);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+fn get_u32(buf: &[u8], at: usize) -> u32 {
+    let mut b = [0u8; 4];
+    b.copy_from_slice(&buf[at..at + 4]);
+    u32::from_le_bytes(b)
+}
+
+fn get_u64(buf: &[u8], at: usize) -> u64 {
+    let mut b = [0u8; 8];
+    b.copy_from_slice(&buf[at..at + 8]);
+    u64::from_le_bytes(b)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u32) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn read_varint(buf: &[u8], at: &mut usize) -> Result<u32, SegmentError> {
+    let mut v: u32 = 0;
+    let mut shift = 0;
+    loop {
+        if *at >= buf.len() || shift > 28 {
+            return Err(SegmentError::Corrupt("truncated varint in postings"));
+        }
+        let b = buf[*at];
+        *at += 1;
+        v |= ((b & 0x7f) as u32) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+        shift += 7;
+    }
+}
+
+// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use invertedindex::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let mut buf = Vec::new();
+        for v in &[0, 1, 127, 128, 300, 16384, u32::MAX] {
+            write_varint(&mut buf, *v);
+        }
+        let mut at = 0;
+        for v in &[0, 1, 127, 128, 300, 16384, u32::MAX] {
+            assert_eq!(read_varint(&buf, &mut at).unwrap(), *v);
+        }
+        assert_eq!(at, buf.len());
+    }
+
+    #[test]
+    fn offsets_out_of_range() {
+        let mut ii = InvertedIndex::new();
+        ii.add_doc(&[b"jury".to_vec()], 1);
+        let path = ::std::env::temp_dir().join(format!("graffiti-offsets-{}.seg", ::std::process::id()));
+        ii.save(&path).unwrap();
+        let segment = Segment::open(&path).unwrap();
+
+        assert_eq!(segment.doc_entry_at(0).map(|d| d.doc), Some(1));
+        assert!(segment.doc_entry_at(1).is_none());
+
+        // a postings block starting where adding its offset overflows, with
+        // the checksums made to match
+        let mut bytes = ::std::fs::read(&path).unwrap();
+        bytes[HEADER_LEN + 12..HEADER_LEN + 20].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        let sections_crc = crc32(&bytes[HEADER_LEN..get_u64(&bytes, 40) as usize]);
+        bytes[48..52].copy_from_slice(&sections_crc.to_le_bytes());
+        let header_crc = crc32(&bytes[..HEADER_LEN - 4]);
+        bytes[HEADER_LEN - 4..HEADER_LEN].copy_from_slice(&header_crc.to_le_bytes());
+        ::std::fs::write(&path, &bytes).unwrap();
+
+        match Segment::open(&path) {
+            Err(SegmentError::Corrupt("lexicon entry out of range")) => {},
+            _ => panic!("expected a corrupt lexicon entry")
+        }
+
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn positions_out_of_range() {
+        // a tf above the positions written makes the next posting's doc
+        // delta read as a position, past the end of u32
+        let terms: Vec<(&[u8], Vec<Posting>)> = vec![(b"jury", vec![
+            Posting { doc: 1, tf: 2, positions: vec![u32::MAX] },
+            Posting { doc: u32::MAX, tf: 0, positions: vec![] },
+        ])];
+        let docs = [DocEntry { doc: 1, len: 1, weight: 1.0 }];
+        let path = ::std::env::temp_dir().join(format!("graffiti-positions-{}.seg", ::std::process::id()));
+        let mut out = ::std::fs::File::create(&path).unwrap();
+        write_segment(&mut out, &terms, &docs, &[], &[], 1).unwrap();
+        let segment = Segment::open(&path).unwrap();
+
+        match segment.postings(0) {
+            Err(SegmentError::Corrupt("doc id or position out of range")) => {},
+            _ => panic!("expected a corrupt position")
+        }
+
+        ::std::fs::remove_file(&path).unwrap();
+    }
+}
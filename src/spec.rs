@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+// Parsing and checking of tokenizer specs. Token definitions are lines of
+//
+//   Name => values
+//
+// and transitions are lines of
+//
+//   From => Type | Type => To
+//
+// Blank lines are skipped. Every part keeps its line and column so errors
+// can point at it.
+
+#[derive(Debug, PartialEq)]
+pub struct SpecError {
+    // set when the spec was loaded from files, see `specfile`
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl SpecError {
+    pub fn new(line: usize, column: usize, message: &str) -> Self {
+        SpecError {
+            file: None,
+            line,
+            column,
+            message: String::from(message)
+        }
+    }
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}, ", file)?;
+        }
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for SpecError {}
+
+// A piece of a spec and where it starts, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spanned<'a> {
+    pub text: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl<'a> Spanned<'a> {
+    // `part` must be a subslice of this piece's text.
+    pub fn sub(&self, part: &'a str) -> Spanned<'a> {
+        Spanned {
+            text: part,
+            line: self.line,
+            column: self.column + (part.as_ptr() as usize - self.text.as_ptr() as usize)
+        }
+    }
+    pub fn trim(&self) -> Spanned<'a> {
+        self.sub(self.text.trim())
+    }
+    pub fn error(&self, message: &str) -> SpecError {
+        SpecError::new(self.line, self.column, message)
+    }
+    fn end(&self) -> Spanned<'a> {
+        self.sub(&self.text[self.text.len()..])
+    }
+}
+
+pub struct TokenDef<'a> {
+    pub name: Spanned<'a>,
+    pub values: Spanned<'a>,
+}
+
+pub struct TransitionDef<'a> {
+    pub from: Spanned<'a>,
+    pub on: Vec<Spanned<'a>>,
+    pub to: Spanned<'a>,
+}
+
+// Non-blank lines split on `=>`, each part trimmed.
+fn split_lines(spec: &str) -> Vec<(Spanned<'_>, Vec<Spanned<'_>>)> {
+    let mut lines = Vec::new();
+    for (i, text) in spec.lines().enumerate() {
+        let line = Spanned { text, line: i + 1, column: 1 };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let parts = text.split("=>").map(|p| line.sub(p).trim()).collect();
+        lines.push((line, parts));
+    }
+    lines
+}
+
+fn name<'a>(part: Spanned<'a>, what: &str) -> Result<Spanned<'a>, SpecError> {
+    if part.text.is_empty() {
+        return Err(part.error(&format!("expected {}", what)));
+    }
+    if part.text.contains(char::is_whitespace) {
+        return Err(part.error(&format!("expected {}, found `{}`", what, part.text)));
+    }
+    Ok(part)
+}
+
+pub fn token_defs(spec: &str) -> Result<Vec<TokenDef<'_>>, SpecError> {
+    let mut defs = Vec::new();
+    for (line, parts) in split_lines(spec) {
+        if parts.len() < 2 {
+            return Err(line.trim().end().error("expected `=>` after the token name"));
+        }
+        if parts.len() > 2 {
+            return Err(parts[2].error("expected one `=>` in a token definition"));
+        }
+        let values = parts[1];
+        if values.text.is_empty() {
+            return Err(values.error("expected token values after `=>`"));
+        }
+        defs.push(TokenDef {
+            name: name(parts[0], "a token name")?,
+            values
+        });
+    }
+    Ok(defs)
+}
+
+pub fn transition_defs(spec: &str) -> Result<Vec<TransitionDef<'_>>, SpecError> {
+    let mut defs = Vec::new();
+    for (line, parts) in split_lines(spec) {
+        if parts.len() < 3 {
+            return Err(line.trim().end().error("expected `From => Type | Type => To`"));
+        }
+        if parts.len() > 3 {
+            return Err(parts[3].error("expected two `=>` in a transition"));
+        }
+        let on = parts[1].text.split('|')
+            .map(|t| name(parts[1].sub(t).trim(), "a token type"))
+            .collect::<Result<Vec<Spanned>, SpecError>>()?;
+        defs.push(TransitionDef {
+            from: name(parts[0], "a state name")?,
+            on,
+            to: name(parts[2], "a state name")?
+        });
+    }
+    Ok(defs)
+}
+
+// Regex rules are lines of `Name = regex`. The regex runs from the first
+// `=` to the end of the line, without surrounding whitespace.
+pub fn rule_defs(spec: &str) -> Result<Vec<TokenDef<'_>>, SpecError> {
+    let mut defs = Vec::new();
+    for (i, text) in spec.lines().enumerate() {
+        let line = Spanned { text, line: i + 1, column: 1 };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let at = match text.find('=') {
+            Some(at) => at,
+            None => return Err(line.trim().end().error("expected `=` after the rule name"))
+        };
+        let values = line.sub(&text[at + 1..]).trim();
+        if values.text.is_empty() {
+            return Err(values.error("expected a regular expression after `=`"));
+        }
+        defs.push(TokenDef {
+            name: name(line.sub(&text[..at]).trim(), "a rule name")?,
+            values
+        });
+    }
+    Ok(defs)
+}
+
+// Checks that the transitions make a usable machine: every type they use
+// is defined, no state goes two ways on the same type, every state they
+// lead to has transitions of its own, and every state can be reached from
+// Start.
+pub fn validate(types: &[&str], transitions: &[TransitionDef]) -> Result<(), SpecError> {
+    let types: HashSet<&str> = types.iter().cloned().collect();
+    let mut out: HashMap<&str, HashMap<&str, &str>> = HashMap::new();
+    for t in transitions {
+        for ty in &t.on {
+            if !types.contains(ty.text) {
+                return Err(ty.error(&format!("undefined token type `{}`", ty.text)));
+            }
+            let targets = out.entry(t.from.text).or_default();
+            match targets.get(ty.text) {
+                Some(&to) if to != t.to.text => {
+                    return Err(ty.error(&format!("`{}` on `{}` already goes to `{}`", t.from.text, ty.text, to)));
+                },
+                _ => {
+                    targets.insert(ty.text, t.to.text);
+                }
+            }
+        }
+    }
+    if !out.contains_key("Start") {
+        return Err(SpecError::new(1, 1, "expected transitions out of `Start`"));
+    }
+    for t in transitions {
+        if !out.contains_key(t.to.text) {
+            return Err(t.to.error(&format!("state `{}` has no transitions out of it", t.to.text)));
+        }
+    }
+    let mut reached: HashSet<&str> = HashSet::new();
+    let mut todo = vec!["Start"];
+    while let Some(s) = todo.pop() {
+        if reached.insert(s) {
+            todo.extend(out[s].values());
+        }
+    }
+    for t in transitions {
+        if !reached.contains(t.from.text) {
+            return Err(t.from.error(&format!("state `{}` cannot be reached from `Start`", t.from.text)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn check(tokens: &str, transitions: &str) -> Result<(), SpecError> {
+        let defs = token_defs(tokens)?;
+        let types: Vec<&str> = defs.iter().map(|d| d.name.text).collect();
+        validate(&types, &transition_defs(transitions)?)
+    }
+
+    fn error_at(tokens: &str, transitions: &str) -> (usize, usize) {
+        let e = check(tokens, transitions).unwrap_err();
+        (e.line, e.column)
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let tokens = "Alpha => 65..91\nNumber 48..58\n";
+        let e = token_defs(tokens).err().unwrap();
+
+        assert_eq!((e.line, e.column), (2, 14));
+        assert_eq!(e.message, "expected `=>` after the token name");
+
+        let e = transition_defs("\n  Start => Alpha |  => Alpha").err().unwrap();
+
+        assert_eq!((e.line, e.column), (2, 19));
+        assert_eq!(e.to_string(), "line 2, column 19: expected a token type");
+        assert!(transition_defs("Start => Alpha => Alpha => Alpha").is_err());
+        assert!(token_defs("Two words => 65").is_err());
+    }
+
+    #[test]
+    fn validation_errors() {
+        let tokens = "Alpha => 65..91\nSpace => 32";
+
+        assert!(check(tokens, "Start => Alpha => Alpha\nAlpha => Alpha => Alpha").is_ok());
+        // undefined type
+        assert_eq!(error_at(tokens, "Start => Alpha => Alpha\nAlpha => Alpah => Alpha"), (2, 10));
+        // Space has no transitions
+        assert_eq!(error_at(tokens, "Start => Alpha => Alpha\nAlpha => Alpha => Alpha\nStart => Space => Space"), (3, 19));
+        // Space cannot be reached
+        assert_eq!(error_at(tokens, "Start => Alpha => Alpha\nAlpha => Alpha => Alpha\nSpace => Space => Space"), (3, 1));
+        // Alpha goes two ways on Alpha
+        assert_eq!(error_at(tokens, "Start => Alpha => Alpha\nAlpha => Alpha => Space\nAlpha => Alpha => Alpha"), (3, 10));
+        // no Start
+        assert_eq!(error_at(tokens, "Alpha => Alpha => Alpha"), (1, 1));
+    }
+}
@@ -0,0 +1,266 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use spec::SpecError;
+
+// Tokenizer specs kept in files. A file has sections holding the formats
+// the tokenizer constructors take:
+//
+//   [tokens]       byte token definitions, for `Tokenizer::new`
+//   [unicode]      character class definitions, for `Tokenizer::unicode`
+//   [transitions]  transitions, used with either of the above
+//   [rules]        regex rules, for `Tokenizer::regex`
+//
+// Lines starting with `#` are comments. `include name` pulls in the lines
+// of another spec where it stands, looking for `name.spec` next to the
+// including file and then in the shipped library (the `specs` directory).
+// An included file's lines before any section header go into the section
+// it was included from.
+
+const LIBRARY: &[(&str, &str)] = &[
+    ("ascii", include_str!("../specs/ascii.spec")),
+    ("brown", include_str!("../specs/brown.spec")),
+    ("csv", include_str!("../specs/csv.spec")),
+    ("english", include_str!("../specs/english.spec")),
+];
+
+pub const SECTIONS: &[&str] = &["tokens", "unicode", "transitions", "rules"];
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Spec(SpecError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io(ref e) => write!(f, "i/o error: {}", e),
+            LoadError::Spec(ref e) => write!(f, "bad spec: {}", e),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+impl From<SpecError> for LoadError {
+    fn from(e: SpecError) -> Self {
+        LoadError::Spec(e)
+    }
+}
+
+// A line of the spec with its includes expanded, and where it came from.
+struct Line {
+    text: String,
+    section: Option<usize>,
+    file: Option<String>,
+    line: usize,
+}
+
+pub struct SpecFile {
+    lines: Vec<Line>,
+}
+
+impl SpecFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let mut spec = SpecFile { lines: Vec::new() };
+        let mut stack = vec![path.display().to_string()];
+        spec.expand(&text, Some(path), &mut stack, None)?;
+        spec.check()?;
+        Ok(spec)
+    }
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, LoadError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        SpecFile::parse(&text)
+    }
+    // A spec not read from a file, so its includes come from the library.
+    pub fn parse(text: &str) -> Result<Self, LoadError> {
+        let mut spec = SpecFile { lines: Vec::new() };
+        spec.expand(text, None, &mut Vec::new(), None)?;
+        spec.check()?;
+        Ok(spec)
+    }
+    // A spec from the shipped library: `brown`, `csv` or `english`.
+    // `ascii` is only there to be included.
+    pub fn library(name: &str) -> Result<Self, LoadError> {
+        let text = match LIBRARY.iter().find(|l| l.0 == name) {
+            Some(l) => l.1,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("no spec named `{}` in the library", name)).into())
+        };
+        let mut spec = SpecFile { lines: Vec::new() };
+        spec.expand(text, None, &mut vec![library_file(name)], None)?;
+        spec.check()?;
+        Ok(spec)
+    }
+    // Appends the lines of `text`, from the file named last on `stack`,
+    // starting in `section`.
+    fn expand(&mut self, text: &str, path: Option<&Path>, stack: &mut Vec<String>, mut section: Option<usize>) -> Result<(), LoadError> {
+        let file = stack.last().cloned();
+        for (i, raw) in text.lines().enumerate() {
+            let trimmed = raw.trim();
+            let at = |column: usize, message: &str| SpecError {
+                file: file.clone(),
+                ..SpecError::new(i + 1, column, message)
+            };
+            let column = raw.len() - raw.trim_start().len() + 1;
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                let name = trimmed[1..trimmed.len() - 1].trim();
+                section = match SECTIONS.iter().position(|&s| s == name) {
+                    Some(s) => Some(s),
+                    None => return Err(at(column, &format!("unknown section `[{}]`", name)).into())
+                };
+                continue;
+            }
+            if let Some(name) = trimmed.strip_prefix("include ") {
+                let name = name.trim();
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                    return Err(at(column, &format!("expected a spec name to include, found `{}`", name)).into());
+                }
+                let (included, text) = match find_include(name, path)? {
+                    Some(found) => found,
+                    None => return Err(at(column, &format!("no spec named `{}` to include", name)).into())
+                };
+                let shown = included.as_ref().map_or_else(|| library_file(name), |p| p.display().to_string());
+                if stack.contains(&shown) {
+                    return Err(at(column, &format!("`{}` includes itself", name)).into());
+                }
+                stack.push(shown);
+                self.expand(&text, included.as_deref(), stack, section)?;
+                stack.pop();
+                continue;
+            }
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if section.is_none() {
+                return Err(at(column, "expected a section header such as `[tokens]` before definitions").into());
+            }
+            self.lines.push(Line { text: String::from(raw), section, file: file.clone(), line: i + 1 });
+        }
+        Ok(())
+    }
+    // Rejects specs that do not make one kind of tokenizer.
+    fn check(&self) -> Result<(), SpecError> {
+        let has = |name: &str| self.section(name).is_some();
+        if has("rules") && (has("tokens") || has("unicode") || has("transitions")) {
+            return Err(SpecError::new(1, 1, "`[rules]` cannot be combined with other sections"));
+        }
+        if has("tokens") && has("unicode") {
+            return Err(SpecError::new(1, 1, "`[tokens]` and `[unicode]` cannot be combined"));
+        }
+        if !has("rules") && !has("transitions") {
+            return Err(SpecError::new(1, 1, "expected a `[transitions]` or `[rules]` section"));
+        }
+        Ok(())
+    }
+    // The lines of a section, if the spec has any, with every other line
+    // of the expanded spec left blank so the parsers' line numbers can be
+    // traced back with `locate`.
+    pub fn section(&self, name: &str) -> Option<String> {
+        let s = SECTIONS.iter().position(|&s| s == name);
+        if !self.lines.iter().any(|l| l.section == s) {
+            return None;
+        }
+        let mut text = String::new();
+        for l in &self.lines {
+            if l.section == s {
+                text.push_str(&l.text);
+            }
+            text.push('\n');
+        }
+        Some(text)
+    }
+    // Points an error in a section's text at the file and line it came
+    // from.
+    pub fn locate(&self, mut e: SpecError) -> SpecError {
+        if let Some(l) = e.line.checked_sub(1).and_then(|i| self.lines.get(i)) {
+            e.file = l.file.clone();
+            e.line = l.line;
+        }
+        e
+    }
+}
+
+fn library_file(name: &str) -> String {
+    format!("specs/{}.spec", name)
+}
+
+// `path` is a file in the directory to look in first.
+fn find_include(name: &str, path: Option<&Path>) -> io::Result<Option<(Option<PathBuf>, String)>> {
+    if let Some(dir) = path.and_then(Path::parent) {
+        let candidate = dir.join(format!("{}.spec", name));
+        if candidate.is_file() {
+            let text = fs::read_to_string(&candidate)?;
+            return Ok(Some((Some(candidate), text)));
+        }
+    }
+    Ok(LIBRARY.iter().find(|l| l.0 == name).map(|l| (None, String::from(l.1))))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn sections_keep_their_lines() {
+        let spec = SpecFile::parse("
+            # comment
+            [tokens]
+            include ascii
+            Slash => 47
+
+            [transitions]
+            Start => Alpha => Alpha
+            ").unwrap();
+        let tokens = spec.section("tokens").unwrap();
+
+        assert!(tokens.contains("Alpha => 97..123"));
+        assert_eq!(spec.section("rules"), None);
+
+        let last = tokens.lines().position(|l| l.contains("Slash")).unwrap();
+        let e = spec.locate(SpecError::new(last + 1, 13, "test"));
+
+        assert_eq!((e.file, e.line), (None, 5));
+
+        let first = tokens.lines().position(|l| l.contains("Alpha")).unwrap();
+        let e = spec.locate(SpecError::new(first + 1, 1, "test"));
+
+        assert_eq!(e.file, Some(String::from("specs/ascii.spec")));
+    }
+
+    #[test]
+    fn load_errors() {
+        let error = |text: &str| match SpecFile::parse(text) {
+            Err(LoadError::Spec(e)) => ((e.line, e.column), e.message),
+            _ => panic!("expected a spec error")
+        };
+
+        assert_eq!(error("[tokens]\n  include nothing"), ((2, 3), String::from("no spec named `nothing` to include")));
+        assert_eq!(error("Alpha => 65..91"), ((1, 1), String::from("expected a section header such as `[tokens]` before definitions")));
+        assert_eq!(error("[token]"), ((1, 1), String::from("unknown section `[token]`")));
+        assert_eq!(error("[rules]\nWord = \\w+\n[tokens]\nAlpha => 65"), ((1, 1), String::from("`[rules]` cannot be combined with other sections")));
+    }
+
+    #[test]
+    fn library_specs_load() {
+        for name in &["brown", "csv", "english"] {
+            assert!(SpecFile::library(name).is_ok(), "{}", name);
+        }
+        assert!(SpecFile::library("ascii").is_err());
+        assert!(SpecFile::library("klingon").is_err());
+    }
+}
@@ -1,31 +1,177 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
 use utils::*;
 
+// Reduces words to stems, so the forms of a word can index as one term.
+// Words are expected in lower case; the stem borrows from the word where
+// it is a prefix of it. Besides the suffix list `Stemmer` there are the
+// `porter`, `porter2` and `lovins` stemmers.
+pub trait Stem {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]>;
+}
+
+// The stem `stem` leaves, borrowed from the word when it is a prefix of it.
+pub fn stem_of<'a>(word: &'a [u8], stem: Vec<u8>) -> Cow<'a, [u8]> {
+    if word.starts_with(&stem) {
+        Cow::Borrowed(&word[..stem.len()])
+    } else {
+        Cow::Owned(stem)
+    }
+}
+
+// A suffix and what it is replaced by, when the stem left once the suffix
+// is taken off meets the rule's conditions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub suffix: Vec<u8>,
+    pub replacement: Vec<u8>,
+    pub min_stem: usize,
+    // whether the stem must have a vowel (a, e, i, o, u, or y not first)
+    pub vowel: bool,
+    // letters the stem must not end with
+    pub not_after: Vec<u8>,
+}
+
+impl Rule {
+    // A rule stripping the suffix from any stem of at least one letter.
+    pub fn new(suffix: &[u8], replacement: &[u8]) -> Self {
+        Rule {
+            suffix: suffix.to_vec(),
+            replacement: replacement.to_vec(),
+            min_stem: 1,
+            vowel: false,
+            not_after: Vec::new()
+        }
+    }
+    pub fn min_stem(mut self, len: usize) -> Self {
+        self.min_stem = len;
+        self
+    }
+    pub fn vowel(mut self, vowel: bool) -> Self {
+        self.vowel = vowel;
+        self
+    }
+    pub fn not_after(mut self, letters: &[u8]) -> Self {
+        self.not_after = letters.to_vec();
+        self
+    }
+    // Whether the rule applies to the word, which must end with its suffix.
+    pub fn holds(&self, word: &[u8]) -> bool {
+        let stem = &word[..word.len() - self.suffix.len()];
+        stem.len() >= self.min_stem
+            && (!self.vowel || stem.iter().enumerate().any(|(i, &b)| b"aeiou".contains(&b) || b == b'y' && i > 0))
+            && !stem.last().is_some_and(|b| self.not_after.contains(b))
+    }
+}
+
+// Stems by a table of rules. The rule for the longest suffix the word ends
+// with applies, or if its conditions do not hold the next longest, and so
+// on; rules for the same suffix are tried in the order they were given.
+//
+// A rules file has a rule per line: the suffix, its replacement, or `-`
+// for none, and any conditions, separated by whitespace. `#` starts a
+// comment.
+//
+//   # suffix  replacement  conditions
+//   sses      ss
+//   ies       i
+//   ing       -            vowel min=3
+//   s         -            not=su
+//
+// `min=N` asks for a stem of at least N letters (1 unless given), `vowel`
+// for a vowel in the stem, and `not=letters` for a stem that does not end
+// with any of the letters.
 pub struct Stemmer {
-    suffix_map: HashMap<u32,Vec<u8>>,
+    suffix_map: HashMap<u32,Vec<Rule>>,
+    longest: usize,
 }
 
 impl Stemmer {
+    // A stemmer stripping any of the suffixes.
     pub fn new(suffixes: &[Vec<u8>]) -> Self {
-        let suffix_map = suffixes.iter()
-            .map(|s| (utils::get_hash_val(s), s.clone()))
-            .collect();
+        Stemmer::rules(suffixes.iter().map(|s| Rule::new(s, b"")))
+    }
+
+    pub fn rules<I: IntoIterator<Item = Rule>>(rules: I) -> Self {
+        let mut suffix_map: HashMap<u32,Vec<Rule>> = HashMap::new();
+        let mut longest = 0;
+        for rule in rules {
+            longest = longest.max(rule.suffix.len());
+            suffix_map.entry(utils::get_hash_val(&rule.suffix)).or_default().push(rule);
+        }
 
         Stemmer {
-            suffix_map: suffix_map,
+            suffix_map,
+            longest
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Stemmer::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("");
+            let mut fields = line.split_whitespace();
+            let (suffix, replacement) = match (fields.next(), fields.next()) {
+                (None, _) => continue,
+                (Some(suffix), Some(replacement)) => (suffix, replacement),
+                (Some(_), None) => return Err(invalid(n + 1, "expected a suffix and its replacement"))
+            };
+            let replacement = if replacement == "-" { "" } else { replacement };
+            let mut rule = Rule::new(suffix.as_bytes(), replacement.as_bytes());
+            for condition in fields {
+                if condition == "vowel" {
+                    rule.vowel = true;
+                } else if let Some(len) = condition.strip_prefix("min=") {
+                    rule.min_stem = len.parse().map_err(|_| invalid(n + 1, &format!("bad stem length `{}`", len)))?;
+                } else if let Some(letters) = condition.strip_prefix("not=") {
+                    rule.not_after = letters.as_bytes().to_vec();
+                } else {
+                    return Err(invalid(n + 1, &format!("unknown condition `{}`", condition)));
+                }
+            }
+            rules.push(rule);
         }
+        Ok(Stemmer::rules(rules))
     }
 
-    pub fn stem<'a>(&self, token: &'a [u8]) -> &'a [u8] {
-        let mut suffix_end = token.len();
-        for c in (0..token.len()).rev() {
-            let suffix_split = &token[c+1..];
-            if let Some(_) = self.suffix_map.get(&utils::get_hash_val(&suffix_split)) {
-                suffix_end -= c;
-                break;
+    // The rule that stems the word, if any.
+    pub fn rule(&self, word: &[u8]) -> Option<&Rule> {
+        for len in (1..=self.longest.min(word.len())).rev() {
+            let suffix = &word[word.len() - len..];
+            if let Some(rules) = self.suffix_map.get(&utils::get_hash_val(suffix)) {
+                if let Some(rule) = rules.iter().find(|r| r.suffix == suffix && r.holds(word)) {
+                    return Some(rule);
+                }
             }
-        };
-        &token[0..suffix_end]
+        }
+        None
+    }
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+impl Stem for Stemmer {
+    fn stem<'a>(&self, word: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.rule(word) {
+            Some(rule) => {
+                let stem = &word[..word.len() - rule.suffix.len()];
+                if rule.replacement.is_empty() {
+                    Cow::Borrowed(stem)
+                } else {
+                    stem_of(word, [stem, &rule.replacement[..]].concat())
+                }
+            },
+            None => Cow::Borrowed(word)
+        }
     }
 }
 
@@ -41,6 +187,45 @@ mod tests {
 
         let v_stemmed = stemmer.stem(&v);
 
-        assert!(v_stemmed == &[97,98,99]);
+        assert!(v_stemmed == &[97,98,99][..]);
+    }
+
+    #[test]
+    fn longest_suffix_first() {
+        let stemmer = Stemmer::new(&[b"s".to_vec(), b"es".to_vec(), b"ings".to_vec()]);
+
+        assert_eq!(stemmer.stem(b"boxes"), &b"box"[..]);
+        assert_eq!(stemmer.stem(b"beginnings"), &b"beginn"[..]);
+        assert_eq!(stemmer.stem(b"s"), &b"s"[..]);
+
+        let stemmer = Stemmer::rules(vec![
+            Rule::new(b"ing", b"").vowel(true).min_stem(3),
+            Rule::new(b"ies", b"y").not_after(b"aeiou"),
+            Rule::new(b"s", b""),
+        ]);
+
+        assert_eq!(stemmer.stem(b"sing"), &b"sing"[..]);
+        assert_eq!(stemmer.stem(b"singing"), &b"sing"[..]);
+        assert_eq!(stemmer.stem(b"ponies"), &b"pony"[..]);
+        assert_eq!(stemmer.stem(b"toeies"), &b"toeie"[..]);
+    }
+
+    #[test]
+    fn rules_file() {
+        let stemmer = Stemmer::open("./test_assets/stemmers/english.rules").unwrap();
+        let stems = [
+            ("caresses", "caress"), ("ponies", "poni"), ("cats", "cat"), ("caress", "caress"),
+            ("thus", "thus"), ("relational", "relate"), ("sing", "sing"), ("walking", "walk"),
+        ];
+
+        for &(word, expected) in &stems {
+            assert_eq!(stemmer.stem(word.as_bytes()), expected.as_bytes(), "{}", word);
+        }
+
+        let e = Stemmer::parse("s -\ning - often\n").err().unwrap();
+
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "line 2: unknown condition `often`");
+        assert_eq!(Stemmer::parse("# only\n\ning\n").err().unwrap().to_string(), "line 3: expected a suffix and its replacement");
     }
 }
@@ -1,140 +1,1018 @@
-use std::collections::HashMap;
-use utils::*;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Read};
+use std::path::Path;
+use std::str;
+use charclass::*;
+use dfa::*;
+use lexicon::*;
+use spec;
+use spec::SpecError;
+use specfile::*;
 
+// Token types and states are ids of their names in the tokenizer's symbol
+// table, see `Tokenizer::type_name` and `Tokenizer::state_name`. Types and
+// states share the table, so a type and a state with the same name have
+// the same id.
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct TokenType(pub u32);
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct State(pub u32);
 
+// Ids every symbol table starts with. TokenType(0) is the type of input
+// no token definition covers.
+const NO_TYPE: &str = "";
+const START: u32 = 1;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Token {
         pub value: Vec<u8>,
         pub t: TokenType,
-        pub s: State
+        pub s: State,
+        // byte offsets of the token in the input, end exclusive; bytes
+        // dropped inside a token are covered by its span but not its value
+        pub start: usize,
+        pub end: usize,
+        // where the token starts, both counted from 1; columns are in bytes
+        pub line: usize,
+        pub column: usize
+}
+
+// A token whose value borrows from the tokenized input. Tokens that had
+// bytes dropped from the middle cannot be a slice of it, so those own
+// their value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TokenRef<'a> {
+        pub value: Cow<'a, [u8]>,
+        pub t: TokenType,
+        pub s: State,
+        pub start: usize,
+        pub end: usize,
+        pub line: usize,
+        pub column: usize
+}
+
+impl<'a> TokenRef<'a> {
+        pub fn into_owned(self) -> Token {
+                Token {
+                        value: self.value.into_owned(),
+                        t: self.t,
+                        s: self.s,
+                        start: self.start,
+                        end: self.end,
+                        line: self.line,
+                        column: self.column
+                }
+        }
+}
+
+impl<'a> From<TokenRef<'a>> for Token {
+        fn from(token: TokenRef<'a>) -> Token {
+                token.into_owned()
+        }
+}
+
+// What a tokenizer does with input no token definition covers: bytes, or
+// characters in Unicode mode, without a type, and with regex rules bytes
+// no rule matches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unmapped {
+        // leave it out; a token it interrupts carries on after it
+        Drop,
+        // make each run of it a token whose type and state are `Unknown`
+        Unknown,
+        // end the token it interrupts, and leave it out
+        Separator,
+        // stop at it, see `Tokenizer::try_tokenize`
+        Error
+}
+
+// The first unmapped input under `Unmapped::Error`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnmappedInput {
+        // the byte, or the character's bytes in Unicode mode
+        pub value: Vec<u8>,
+        pub offset: usize,
+        pub line: usize,
+        pub column: usize
+}
+
+impl fmt::Display for UnmappedInput {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if self.value.len() == 1 {
+                        write!(f, "no token type for byte 0x{:02x}", self.value[0])?;
+                } else {
+                        write!(f, "no token type for `{}`", String::from_utf8_lossy(&self.value))?;
+                }
+                write!(f, " at line {}, column {} (offset {})", self.line, self.column, self.offset)
+        }
+}
+
+impl Error for UnmappedInput {}
+
+// Tokens with the number of input bytes left out of them, whether they
+// were unmapped or had no transition to take.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Tokenized<T> {
+        pub tokens: Vec<T>,
+        pub dropped: usize
 }
 
 pub struct Tokenizer {
-        token_map: HashMap<u8, TokenType>,
-        transition_map: HashMap<State, HashMap<TokenType, State>>
+        symbols: Lexicon,
+        table: Table,
+        // set in Unicode mode, where input is decoded as UTF-8 and token
+        // types are given per character
+        classes: Option<CharClasses>,
+        // set when tokens are defined by regex rules, see `dfa`
+        rules: Option<Dfa>,
+        unmapped: Unmapped,
+        // bytes the last `tokenize` dropped
+        dropped: usize
 }
 
 impl Tokenizer {
-        pub fn new(token_map: &str, transition_map: &str) -> Self {
-                Tokenizer {
-                        token_map: Tokenizer::compile_tokens_ascii(token_map),
-                        transition_map: Tokenizer::compile_states(transition_map)
+        pub fn new(token_map: &str, transition_map: &str) -> Result<Self, SpecError> {
+                Tokenizer::check(token_map, transition_map, &[])?;
+                let mut symbols = Tokenizer::symbol_table();
+                let token_map = Tokenizer::compile_tokens_ascii(token_map, &mut symbols)?;
+                let transition_map = Tokenizer::compile_states(transition_map, &mut symbols)?;
+                Ok(Tokenizer {
+                        table: Table::new(|b| token_map.get(&b).cloned(), &transition_map),
+                        classes: None,
+                        rules: None,
+                        unmapped: Unmapped::Drop,
+                        dropped: 0,
+                        symbols
+                })
+        }
+        // A tokenizer whose token definitions are Unicode character classes,
+        // see `charclass`. Bytes that are not valid UTF-8 get the `Invalid`
+        // token type, so transitions can match them like any other type;
+        // where none does they end the current token and are dropped.
+        pub fn unicode(token_map: &str, transition_map: &str) -> Result<Self, SpecError> {
+                Tokenizer::check(token_map, transition_map, &[INVALID_NAME])?;
+                let mut symbols = Tokenizer::symbol_table();
+                let classes = CharClasses::compile(token_map, &mut symbols)?;
+                let transition_map = Tokenizer::compile_states(transition_map, &mut symbols)?;
+                Ok(Tokenizer {
+                        // bytes above 127 never reach the table on their own
+                        table: Table::new(|b| if b < 128 { Some(classes.classify(b as char)) } else { None }, &transition_map),
+                        classes: Some(classes),
+                        rules: None,
+                        unmapped: Unmapped::Drop,
+                        dropped: 0,
+                        symbols
+                })
+        }
+        // A tokenizer whose tokens are defined by regex rules, one per line:
+        //
+        //   Word = [A-Za-z]+('[a-z]+)?
+        //   Number = \d+(\.\d+)?
+        //
+        // At each point it takes the longest match of any rule, and of rules
+        // matching the same length the one defined first. A token's type and
+        // state are both named after its rule. Bytes no rule matches are
+        // dropped.
+        pub fn regex(rules: &str) -> Result<Self, SpecError> {
+                let mut symbols = Tokenizer::symbol_table();
+                Ok(Tokenizer {
+                        table: Table::new(|_| None, &HashMap::new()),
+                        classes: None,
+                        rules: Some(Dfa::compile(rules, &mut symbols)?),
+                        unmapped: Unmapped::Drop,
+                        dropped: 0,
+                        symbols
+                })
+        }
+        // A tokenizer from a spec file, see `specfile` for the format.
+        pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+                Ok(Tokenizer::from_spec(&SpecFile::open(path)?)?)
+        }
+        pub fn from_reader<R: Read>(reader: R) -> Result<Self, LoadError> {
+                Ok(Tokenizer::from_spec(&SpecFile::from_reader(reader)?)?)
+        }
+        // A tokenizer for a spec from the shipped library, such as `brown`.
+        pub fn standard(name: &str) -> Result<Self, LoadError> {
+                Ok(Tokenizer::from_spec(&SpecFile::library(name)?)?)
+        }
+        pub fn from_spec(spec: &SpecFile) -> Result<Self, SpecError> {
+                let section = |name| spec.section(name).unwrap_or_default();
+                let tokenizer = if let Some(rules) = spec.section("rules") {
+                        Tokenizer::regex(&rules)
+                } else if let Some(classes) = spec.section("unicode") {
+                        Tokenizer::unicode(&classes, &section("transitions"))
+                } else {
+                        Tokenizer::new(&section("tokens"), &section("transitions"))
+                };
+                tokenizer.map_err(|e| spec.locate(e))
+        }
+        // How to handle unmapped input; by default it is dropped.
+        pub fn unmapped(mut self, policy: Unmapped) -> Self {
+                self.unmapped = policy;
+                self
+        }
+        // An empty symbol table, with the names the machine relies on at
+        // their fixed ids.
+        pub fn symbol_table() -> Lexicon {
+                let mut symbols = Lexicon::new();
+                for name in &[NO_TYPE, "Start", INVALID_NAME, UNKNOWN_NAME] {
+                        symbols.intern(name.as_bytes());
                 }
+                symbols
         }
-        pub fn compile_states(transition_map: &str) -> HashMap<State, HashMap<TokenType, State>> {
+        fn check(token_map: &str, transition_map: &str, builtin: &[&str]) -> Result<(), SpecError> {
+                let defs = spec::token_defs(token_map)?;
+                let mut types: Vec<&str> = defs.iter().map(|d| d.name.text).collect();
+                types.extend_from_slice(builtin);
+                spec::validate(&types, &spec::transition_defs(transition_map)?)
+        }
+        pub fn compile_states(transition_map: &str, symbols: &mut Lexicon) -> Result<HashMap<State, HashMap<TokenType, State>>, SpecError> {
                 let mut parsed_trans = HashMap::new();
-                for t in transition_map.lines() {
-                        let v: Vec<&str> = t.split("=>").map(|s| s.trim()).collect();
-                        if v[0].is_empty() {
-                                continue;
-                        }
-                        let v_bytes: Vec<u8> = v[0].bytes().collect();
-                        let start_state: State = State(utils::get_hash_val(&v_bytes));
-                        let tokens: Vec<&str> = v[1].split("|").map(|t| t.trim()).collect();
-			let v2_bytes: Vec<u8> = v[2].bytes().collect();
-                        let end_state: State = State(utils::get_hash_val(&v2_bytes));
-
-                        let x = parsed_trans.entry(start_state).or_insert(HashMap::new());
-                        for t in tokens {
-				let t_bytes: Vec<u8> = t.bytes().collect();
-                                let token_type = TokenType(utils::get_hash_val(&t_bytes));
-                                (*x).entry(token_type).or_insert(end_state.to_owned());
+                for t in spec::transition_defs(transition_map)? {
+                        let start_state = State(symbols.intern(t.from.text.as_bytes()).0);
+                        let end_state = State(symbols.intern(t.to.text.as_bytes()).0);
+
+                        let x = parsed_trans.entry(start_state).or_insert_with(HashMap::new);
+                        for ty in t.on {
+                                let token_type = TokenType(symbols.intern(ty.text.as_bytes()).0);
+                                x.entry(token_type).or_insert_with(|| end_state.to_owned());
                         }
                 };
-                parsed_trans
+                Ok(parsed_trans)
         }
-        pub fn compile_tokens_ascii(tokens: &str) -> HashMap<u8, TokenType> {
+        // Values are byte values: `65`, a list `9,10,13,32`, or a range
+        // `65..91` with the end excluded.
+        pub fn compile_tokens_ascii(tokens: &str, symbols: &mut Lexicon) -> Result<HashMap<u8, TokenType>, SpecError> {
                 let mut token_map = HashMap::new();
-                for t in tokens.lines() {
-                        let v: Vec<&str> = t.split("=>").map(|s| s.trim()).collect();
-                        if v[0].is_empty() {
-                                continue;
-                        }
+                for def in spec::token_defs(tokens)? {
+                        let byte = |part: &str, max: u16| {
+                                let part = def.values.sub(part).trim();
+                                part.text.parse::<u16>().ok().filter(|&b| b <= max)
+                                        .ok_or_else(|| part.error(&format!("expected a byte value, found `{}`", part.text)))
+                        };
                         let mut vals: Vec<u8> = Vec::new();
-                        if v[1].contains("..") {
-                                let bounds: Vec<u8> = v[1].split("..").map(|c| c.parse::<u8>().unwrap()).collect();
-                                vals.append(&mut (bounds[0]..bounds[1]).collect());
-                        } else if v[1].contains(',') {
-				let mut nums: Vec<u8> = v[1].split(',').map(|i| i.parse::<u8>().unwrap()).collect();
-                                vals.append(&mut nums);
+                        let text = def.values.text;
+                        if let Some(at) = text.find("..") {
+                                let (start, end) = (byte(&text[..at], 255)?, byte(&text[at + 2..], 256)?);
+                                if start >= end {
+                                        return Err(def.values.error(&format!("empty range `{}`", text)));
+                                }
+                                vals.extend((start..end).map(|b| b as u8));
                         } else {
-                                let single_num = v[1].parse::<u8>().unwrap(); 
-                                vals.push(single_num);
+                                for item in text.split(',') {
+                                        vals.push(byte(item, 255)? as u8);
+                                }
                         }
-                        let t_bytes: Vec<u8> = v[0].bytes().collect();
+                        let token_type = TokenType(symbols.intern(def.name.text.as_bytes()).0);
                         for u in vals {
-                                token_map.insert(u, TokenType(utils::get_hash_val(&t_bytes)));
+                                token_map.insert(u, token_type.clone());
                         }
                 };
-                token_map
+                Ok(token_map)
+        }
+        // The compiled machine, for inspecting a spec; see `graph`. States
+        // come in table order with Start first, and types in class order.
+        // Tokenizers defined by regex rules have no states or types here,
+        // only `rules`.
+        pub fn states(&self) -> &[State] {
+                if self.rules.is_some() { &[] } else { &self.table.states }
+        }
+        pub fn token_types(&self) -> &[TokenType] {
+                &self.table.types[2..]
+        }
+        pub fn next_state(&self, s: &State, t: &TokenType) -> Option<&State> {
+                let from = self.table.states.iter().position(|x| x == s)?;
+                let class = *self.table.class_ids.get(t)?;
+                self.table.next(from as u32, class).map(|to| &self.table.states[to as usize])
+        }
+        // The type of a byte, in Unicode mode only for ASCII bytes.
+        pub fn byte_type(&self, b: u8) -> Option<&TokenType> {
+                match self.table.byte_classes[b as usize] {
+                        UNMAPPED => None,
+                        class => Some(&self.table.types[class as usize])
+                }
+        }
+        pub fn is_unicode(&self) -> bool {
+                self.classes.is_some()
+        }
+        pub fn rules(&self) -> Option<&Dfa> {
+                self.rules.as_ref()
+        }
+        pub fn type_name(&self, t: &TokenType) -> &str {
+                self.symbol(t.0)
+        }
+        pub fn state_name(&self, s: &State) -> &str {
+                self.symbol(s.0)
         }
+        fn symbol(&self, id: u32) -> &str {
+                str::from_utf8(self.symbols.term(TermId(id))).expect("symbols are interned from str")
+        }
+        // The type or state with this name, if the spec has one.
+        pub fn token_type(&self, name: &str) -> Option<TokenType> {
+                self.symbols.get(name.as_bytes()).map(|id| TokenType(id.0))
+        }
+        pub fn state(&self, name: &str) -> Option<State> {
+                self.symbols.get(name.as_bytes()).map(|id| State(id.0))
+        }
+        // Wraps a token, type or state so it displays with its names.
+        pub fn named<'a, T>(&'a self, item: &'a T) -> Named<'a, T> {
+                Named { tokenizer: self, item }
+        }
+        // Under `Unmapped::Error`, `tokenize` and `tokenize_ref` panic at
+        // unmapped input; use `try_tokenize` with that policy.
         pub fn tokenize(&mut self, token_str: &[u8]) -> Vec<Token> {
-                let mut tokens: Vec<Token> = Vec::new();
-
-                let mut curr_token: Vec<u8> = Vec::new();
-                let mut raw_bytes = token_str.to_vec();
-                let mut curr_state = State(utils::get_hash_val(b"Start"));
-                let mut last_byte_t = TokenType(0);
-
-                while !raw_bytes.is_empty() {
-                        //println!("Byte: {:?}", raw_bytes[0]);
-                        //println!("curr_token: {:?}", curr_token);
-                        //println!("curr_state: {:?}", curr_state);
-                        //println!("tokens: {:?}", tokens);    
-
-                        let curr_byte = raw_bytes[0];
-                        let curr_byte_t = match self.token_map.get(&curr_byte) {
-                                None => TokenType(0),
-                                Some(v) => v.to_owned()
+                let tokenized = self.tokenize_checked(token_str);
+                self.dropped = tokenized.dropped;
+                tokenized.tokens.into_iter().map(TokenRef::into_owned).collect()
+        }
+        // Bytes the last `tokenize` left out, counted as `try_tokenize` does.
+        pub fn dropped(&self) -> usize {
+                self.dropped
+        }
+        // Like `tokenize`, but token values borrow from the input instead of
+        // being copied.
+        pub fn tokenize_ref<'a>(&self, token_str: &'a [u8]) -> Vec<TokenRef<'a>> {
+                self.tokenize_checked(token_str).tokens
+        }
+        fn tokenize_checked<'a>(&self, token_str: &'a [u8]) -> Tokenized<TokenRef<'a>> {
+                match self.try_tokenize_ref(token_str) {
+                        Ok(tokenized) => tokenized,
+                        Err(e) => panic!("{}; use `try_tokenize` under `Unmapped::Error`", e)
+                }
+        }
+        // Like `tokenize`, but also counts the bytes dropped, and under
+        // `Unmapped::Error` fails at the first unmapped input.
+        pub fn try_tokenize(&self, token_str: &[u8]) -> Result<Tokenized<Token>, UnmappedInput> {
+                let tokenized = self.try_tokenize_ref(token_str)?;
+                Ok(Tokenized {
+                        tokens: tokenized.tokens.into_iter().map(TokenRef::into_owned).collect(),
+                        dropped: tokenized.dropped
+                })
+        }
+        pub fn try_tokenize_ref<'a>(&self, token_str: &'a [u8]) -> Result<Tokenized<TokenRef<'a>>, UnmappedInput> {
+                let (tokens, machine) = self.run(token_str);
+                match machine.error {
+                        Some(e) => Err(e),
+                        None => Ok(Tokenized { tokens, dropped: machine.dropped })
+                }
+        }
+        fn run<'a>(&self, token_str: &'a [u8]) -> (Vec<TokenRef<'a>>, Machine) {
+                let mut spans: Vec<Span> = Vec::new();
+                let mut machine = Machine::new(false);
+                for &b in token_str {
+                        machine.push(self, b, &mut spans);
+                }
+                machine.finish(self, &mut spans);
+                let tokens = spans.into_iter().map(|span| {
+                        let value = if span.gaps.is_empty() {
+                                Cow::Borrowed(&token_str[span.start..span.end])
+                        } else {
+                                let mut value = Vec::with_capacity(span.end - span.start);
+                                let mut at = span.start;
+                                for &(start, end) in &span.gaps {
+                                        value.extend_from_slice(&token_str[at..start]);
+                                        at = end;
+                                }
+                                value.extend_from_slice(&token_str[at..span.end]);
+                                Cow::Owned(value)
                         };
+                        TokenRef {
+                                value,
+                                t: span.t,
+                                s: span.s,
+                                start: span.start,
+                                end: span.end,
+                                line: span.line,
+                                column: span.column
+                        }
+                }).collect();
+                (tokens, machine)
+        }
+        // Tokenizes a reader a buffer at a time, yielding the same tokens as
+        // `tokenize` would for its whole contents.
+        pub fn stream<R: BufRead>(&self, reader: R) -> TokenStream<'_, R> {
+                TokenStream {
+                        tokenizer: self,
+                        reader,
+                        machine: Machine::new(true),
+                        pending: VecDeque::new(),
+                        done: false,
+                        error: None
+                }
+        }
+}
+
+pub struct Named<'a, T: 'a> {
+        tokenizer: &'a Tokenizer,
+        item: &'a T
+}
+
+impl<'a> fmt::Display for Named<'a, TokenType> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.tokenizer.type_name(self.item))
+        }
+}
+
+impl<'a> fmt::Display for Named<'a, State> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(self.tokenizer.state_name(self.item))
+        }
+}
 
-                        let new_state = match self.transition_map.get(&curr_state).unwrap().get(&curr_byte_t) {
+// `Pos(Alpha) "bar" at 1:5`: the state, then the type of its last unit.
+impl<'a> fmt::Display for Named<'a, Token> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let token = self.item;
+                write!(f, "{}({}) {:?} at {}:{}",
+                        self.tokenizer.state_name(&token.s),
+                        self.tokenizer.type_name(&token.t),
+                        String::from_utf8_lossy(&token.value),
+                        token.line,
+                        token.column)
+        }
+}
+
+// The transitions of a byte or Unicode mode tokenizer as a dense table,
+// so the machine takes one array lookup per unit instead of two hash
+// lookups. Token types get dense class ids, looked up per byte through a
+// 256-entry map; states get dense ids with Start at 0. Types cannot be
+// merged further, since tokens report them, nor can differently named
+// states, so the table is as small as the spec allows.
+struct Table {
+        // class of each byte; in Unicode mode only ASCII bytes are looked
+        // up here
+        byte_classes: [u16; 256],
+        // class ids of the types transitions use, for Unicode characters
+        class_ids: HashMap<TokenType, u16>,
+        types: Vec<TokenType>,
+        states: Vec<State>,
+        // `types.len()` entries per state
+        next: Vec<u32>
+}
+
+// Class of bytes no token definition covers, which are dropped, and the
+// class of the other types that no transition uses.
+const UNMAPPED: u16 = 0;
+const UNUSED: u16 = 1;
+const START_ID: u32 = 0;
+const NO_STATE: u32 = u32::MAX;
+
+impl Table {
+        fn new<F: Fn(u8) -> Option<TokenType>>(byte_type: F, transition_map: &HashMap<State, HashMap<TokenType, State>>) -> Self {
+                let mut types: Vec<TokenType> = (0..=255u8).filter_map(&byte_type)
+                        .chain(transition_map.values().flat_map(|m| m.keys().cloned()))
+                        .filter(|t| t.0 != 0)
+                        .collect();
+                types.sort_by_key(|t| t.0);
+                types.dedup();
+                types.splice(0..0, vec![TokenType(0), TokenType(0)]);
+                let class_ids: HashMap<TokenType, u16> = types.iter().enumerate().skip(2).map(|(i, t)| (t.clone(), i as u16)).collect();
+
+                let mut byte_classes = [UNMAPPED; 256];
+                for b in 0..=255u8 {
+                        if let Some(t) = byte_type(b) {
+                                byte_classes[b as usize] = class_ids.get(&t).cloned().unwrap_or(UNMAPPED);
+                        }
+                }
+
+                let mut states = vec![State(START)];
+                let mut others: Vec<State> = transition_map.iter()
+                        .flat_map(|(from, m)| m.values().chain(Some(from)))
+                        .filter(|s| s.0 != START)
+                        .cloned()
+                        .collect();
+                others.sort_by_key(|s| s.0);
+                others.dedup();
+                states.extend(others);
+                let state_ids: HashMap<&State, u32> = states.iter().enumerate().map(|(i, s)| (s, i as u32)).collect();
+
+                let mut next = vec![NO_STATE; states.len() * types.len()];
+                for (from, m) in transition_map {
+                        for (t, to) in m {
+                                next[state_ids[from] as usize * types.len() + class_ids[t] as usize] = state_ids[to];
+                        }
+                }
+                Table { byte_classes, class_ids, types, states, next }
+        }
+        fn next(&self, state: u32, class: u16) -> Option<u32> {
+                match self.next[state as usize * self.types.len() + class as usize] {
+                        NO_STATE => None,
+                        to => Some(to)
+                }
+        }
+        // The class of a type from a character class lookup.
+        fn class_of(&self, t: &TokenType) -> u16 {
+                if t.0 == 0 {
+                        return UNMAPPED;
+                }
+                self.class_ids.get(t).cloned().unwrap_or(UNUSED)
+        }
+}
+
+// A token as the machine ends it. Its bytes are only collected when the
+// input is not around to slice them from afterwards.
+struct Span {
+        t: TokenType,
+        s: State,
+        value: Vec<u8>,
+        // byte ranges dropped from inside the span
+        gaps: Vec<(usize, usize)>,
+        start: usize,
+        end: usize,
+        line: usize,
+        column: usize
+}
+
+impl Span {
+        fn into_token(self) -> Token {
+                Token {
+                        value: self.value,
+                        t: self.t,
+                        s: self.s,
+                        start: self.start,
+                        end: self.end,
+                        line: self.line,
+                        column: self.column
+                }
+        }
+}
+
+// The tokenizer's state between input bytes, so tokens can straddle the
+// buffers of a stream.
+struct Machine {
+        keep_bytes: bool,
+        utf8: Utf8Decoder,
+        curr_token: Vec<u8>,
+        curr_len: usize,
+        curr_gaps: Vec<(usize, usize)>,
+        // dense id in the tokenizer's table
+        curr_state: u32,
+        last_byte_t: TokenType,
+        consumed: bool,
+        offset: usize,
+        token_start: usize,
+        token_end: usize,
+        // line and column at `offset`, and where the current token starts
+        pos: (usize, usize),
+        token_pos: (usize, usize),
+        // in regex mode, the bytes read since the current match started,
+        // how many of them the DFA has run over, and the longest match so
+        // far with its type
+        pending: Vec<u8>,
+        scanned: usize,
+        dfa_state: usize,
+        accepted: Option<(usize, TokenType)>,
+        // bytes left out of every token so far
+        dropped: usize,
+        // set while in a run of unmapped input under `Unmapped::Unknown`
+        unknown: bool,
+        // the unmapped input that stopped the machine under
+        // `Unmapped::Error`; later input is ignored
+        error: Option<UnmappedInput>
+}
+
+impl Machine {
+        fn new(keep_bytes: bool) -> Self {
+                Machine {
+                        curr_state: START_ID,
+                        keep_bytes,
+                        utf8: Utf8Decoder::default(),
+                        curr_token: Vec::new(),
+                        curr_len: 0,
+                        curr_gaps: Vec::new(),
+                        last_byte_t: TokenType(0),
+                        consumed: false,
+                        offset: 0,
+                        token_start: 0,
+                        token_end: 0,
+                        pos: (1, 1),
+                        token_pos: (1, 1),
+                        pending: Vec::new(),
+                        scanned: 0,
+                        dfa_state: 0,
+                        accepted: None,
+                        dropped: 0,
+                        unknown: false,
+                        error: None
+                }
+        }
+        fn push(&mut self, tokenizer: &Tokenizer, b: u8, spans: &mut Vec<Span>) {
+                if self.error.is_some() {
+                        return;
+                }
+                if let Some(ref dfa) = tokenizer.rules {
+                        self.pending.push(b);
+                        self.scan(tokenizer, dfa, false, spans);
+                        return;
+                }
+                match tokenizer.classes {
+                        None => {
+                                let class = tokenizer.table.byte_classes[b as usize];
+                                self.step(tokenizer, &tokenizer.table.types[class as usize], class, &[b], spans);
+                        },
+                        Some(ref classes) => {
+                                let mut units = [Utf8Unit::Invalid(0); 4];
+                                let n = self.utf8.push(b, &mut units);
+                                for unit in &units[..n] {
+                                        self.step_unit(tokenizer, classes, *unit, spans);
+                                }
+                        }
+                }
+        }
+        fn step_unit(&mut self, tokenizer: &Tokenizer, classes: &CharClasses, unit: Utf8Unit, spans: &mut Vec<Span>) {
+                match unit {
+                        Utf8Unit::Char(c) => {
+                                let mut buf = [0; 4];
+                                let (t, class) = if (c as u32) < 128 {
+                                        let class = tokenizer.table.byte_classes[c as usize];
+                                        (tokenizer.table.types[class as usize].clone(), class)
+                                } else {
+                                        let t = classes.classify(c);
+                                        let class = tokenizer.table.class_of(&t);
+                                        (t, class)
+                                };
+                                self.step(tokenizer, &t, class, c.encode_utf8(&mut buf).as_bytes(), spans);
+                        },
+                        Utf8Unit::Invalid(b) => {
+                                let t = TokenType(INVALID);
+                                self.step(tokenizer, &t, tokenizer.table.class_of(&t), &[b], spans);
+                        }
+                }
+        }
+        // A unit (a byte, or a character in Unicode mode) can take several
+        // transitions before it is consumed: ending the current token sends
+        // the machine back to Start, and leaving Start does not consume
+        // anything. Units with no token type, or with no transition out of
+        // Start, are dropped, unless the tokenizer has another policy for
+        // units with no type.
+        fn step(&mut self, tokenizer: &Tokenizer, curr_byte_t: &TokenType, class: u16, unit: &[u8], spans: &mut Vec<Span>) {
+                if self.error.is_some() {
+                        return;
+                }
+                if class == UNMAPPED && tokenizer.unmapped != Unmapped::Drop {
+                        self.unmapped(tokenizer, unit, spans);
+                        if self.error.is_none() {
+                                self.advance(unit);
+                        }
+                        return;
+                }
+                if self.unknown {
+                        self.emit_unknown(spans);
+                }
+                loop {
+                        let mut consumed = false;
+                        let new_state = match tokenizer.table.next(self.curr_state, class) {
                                 None => {
-                                        let start_state = State(utils::get_hash_val(b"Start"));
-                                        let mut s = &curr_state;
-                                        if curr_byte_t != TokenType(0) {
-                                                tokens.push(Token { 
-                                                        t: last_byte_t.to_owned(), 
-                                                        s: curr_state.to_owned(),
-                                                        value: curr_token.to_owned()});
-                                                curr_token.clear();                                          
-                                                s = &start_state;
+                                        if class != UNMAPPED && self.curr_state != START_ID {
+                                                self.emit(tokenizer, spans);
+                                                START_ID
                                         } else {
-                                                raw_bytes.remove(0);
-                                        };
-                                        s.to_owned()
+                                                let dropped = self.offset;
+                                                self.dropped += unit.len();
+                                                self.advance(unit);
+                                                consumed = true;
+                                                if self.curr_len == 0 {
+                                                        self.token_start = self.offset;
+                                                        self.token_pos = self.pos;
+                                                } else {
+                                                        self.curr_gaps.push((dropped, self.offset));
+                                                }
+                                                self.curr_state
+                                        }
                                 },
                                 Some(v) => {
-                                        if v == &curr_state && curr_state != State(utils::get_hash_val(b"Start")) {
-                                                curr_token.push(raw_bytes.remove(0));
+                                        if v == self.curr_state && self.curr_state != START_ID {
+                                                if self.curr_len == 0 {
+                                                        self.token_start = self.offset;
+                                                        self.token_pos = self.pos;
+                                                }
+                                                self.advance(unit);
+                                                consumed = true;
+                                                if self.keep_bytes {
+                                                        self.curr_token.extend_from_slice(unit);
+                                                }
+                                                self.curr_len += unit.len();
+                                                self.token_end = self.offset;
+                                        }
+                                        if v != self.curr_state && self.curr_state != START_ID {
+                                                self.emit(tokenizer, spans);
                                         }
-                                        if v != &curr_state && curr_state != State(utils::get_hash_val(b"Start")) {
-                                                tokens.push(Token { 
-                                                        t: last_byte_t.to_owned(), 
-                                                        s: curr_state.to_owned(),
-                                                        value: curr_token.to_owned()});
-                                                curr_token.clear();
-                                        };
-                                        v.to_owned()
-                                } 
+                                        v
+                                }
                         };
+                        self.last_byte_t = curr_byte_t.clone();
+                        self.curr_state = new_state;
+                        if consumed {
+                                self.consumed = true;
+                                return;
+                        }
+                }
+        }
+        // Runs the DFA over the pending bytes. Where it gets stuck, or at the
+        // end of the input, the longest match found becomes a token and the
+        // bytes after it are scanned again; with no match the first pending
+        // byte is unmapped instead.
+        fn scan(&mut self, tokenizer: &Tokenizer, dfa: &Dfa, at_end: bool, spans: &mut Vec<Span>) {
+                loop {
+                        while self.scanned < self.pending.len() {
+                                match dfa.next(self.dfa_state, self.pending[self.scanned]) {
+                                        Some(next) => {
+                                                self.dfa_state = next;
+                                                self.scanned += 1;
+                                                if let Some(t) = dfa.accept(next) {
+                                                        self.accepted = Some((self.scanned, t.clone()));
+                                                }
+                                        },
+                                        None => break
+                                }
+                        }
+                        let stuck = self.scanned < self.pending.len();
+                        if !stuck && (!at_end || self.pending.is_empty()) {
+                                return;
+                        }
+                        let len = match self.accepted.take() {
+                                Some((len, t)) => {
+                                        if self.unknown {
+                                                self.emit_unknown(spans);
+                                        }
+                                        spans.push(Span {
+                                                s: State(t.0),
+                                                t,
+                                                value: if self.keep_bytes { self.pending[..len].to_vec() } else { Vec::new() },
+                                                gaps: Vec::new(),
+                                                start: self.offset,
+                                                end: self.offset + len,
+                                                line: self.pos.0,
+                                                column: self.pos.1
+                                        });
+                                        len
+                                },
+                                None => {
+                                        let b = self.pending[0];
+                                        self.unmapped(tokenizer, &[b], spans);
+                                        if self.error.is_some() {
+                                                return;
+                                        }
+                                        1
+                                }
+                        };
+                        for b in self.pending.drain(..len) {
+                                self.offset += 1;
+                                self.pos = if b == b'\n' { (self.pos.0 + 1, 1) } else { (self.pos.0, self.pos.1 + 1) };
+                        }
+                        self.scanned = 0;
+                        self.dfa_state = dfa.start();
+                }
+        }
+        // The token still being built when the input ends.
+        fn finish(&mut self, tokenizer: &Tokenizer, spans: &mut Vec<Span>) {
+                if let Some(ref dfa) = tokenizer.rules {
+                        if self.error.is_none() {
+                                self.scan(tokenizer, dfa, true, spans);
+                        }
+                        if self.unknown {
+                                self.emit_unknown(spans);
+                        }
+                        return;
+                }
+                if let (Some(ref classes), None) = (&tokenizer.classes, &self.error) {
+                        let mut units = [Utf8Unit::Invalid(0); 4];
+                        let n = self.utf8.finish(&mut units);
+                        for unit in &units[..n] {
+                                self.step_unit(tokenizer, classes, *unit, spans);
+                        }
+                }
+                if self.unknown {
+                        self.emit_unknown(spans);
+                }
+                if self.consumed {
+                        self.emit(tokenizer, spans);
+                        self.consumed = false;
+                }
+        }
+        fn emit(&mut self, tokenizer: &Tokenizer, spans: &mut Vec<Span>) {
+                let end = if self.curr_len == 0 { self.token_start } else { self.token_end };
+                // bytes dropped after the last one kept are outside the span
+                self.curr_gaps.retain(|g| g.1 <= end);
+                spans.push(Span {
+                        t: self.last_byte_t.to_owned(),
+                        s: tokenizer.table.states[self.curr_state as usize].clone(),
+                        value: self.curr_token.split_off(0),
+                        gaps: self.curr_gaps.split_off(0),
+                        start: self.token_start,
+                        end,
+                        line: self.token_pos.0,
+                        column: self.token_pos.1
+                });
+                self.curr_len = 0;
+                self.token_start = self.offset;
+                self.token_pos = self.pos;
+        }
+        // Handles a unit with no type by the tokenizer's policy, before the
+        // machine advances over it. Under `Unmapped::Drop` only regex rule
+        // tokenizers get here; the others drop such units in `step`, where
+        // they can fall inside a token.
+        fn unmapped(&mut self, tokenizer: &Tokenizer, unit: &[u8], spans: &mut Vec<Span>) {
+                if tokenizer.unmapped == Unmapped::Error {
+                        self.error = Some(UnmappedInput {
+                                value: unit.to_vec(),
+                                offset: self.offset,
+                                line: self.pos.0,
+                                column: self.pos.1
+                        });
+                        return;
+                }
+                if self.curr_state != START_ID {
+                        self.emit(tokenizer, spans);
+                        self.curr_state = START_ID;
+                }
+                // nothing is left for `finish` to end
+                self.consumed = false;
+                if tokenizer.unmapped != Unmapped::Unknown {
+                        self.dropped += unit.len();
+                        return;
+                }
+                if !self.unknown {
+                        self.unknown = true;
+                        self.token_start = self.offset;
+                        self.token_pos = self.pos;
+                }
+                if self.keep_bytes {
+                        self.curr_token.extend_from_slice(unit);
+                }
+        }
+        fn emit_unknown(&mut self, spans: &mut Vec<Span>) {
+                spans.push(Span {
+                        t: TokenType(UNKNOWN),
+                        s: State(UNKNOWN),
+                        value: self.curr_token.split_off(0),
+                        gaps: Vec::new(),
+                        start: self.token_start,
+                        end: self.offset,
+                        line: self.token_pos.0,
+                        column: self.token_pos.1
+                });
+                self.unknown = false;
+                self.token_start = self.offset;
+                self.token_pos = self.pos;
+        }
+        fn advance(&mut self, unit: &[u8]) {
+                self.offset += unit.len();
+                self.pos = if unit == b"\n" {
+                        (self.pos.0 + 1, 1)
+                } else {
+                        (self.pos.0, self.pos.1 + unit.len())
+                };
+        }
+}
+
+// Token type given to bytes that are not valid UTF-8 in Unicode mode.
+pub const INVALID_NAME: &str = "Invalid";
+const INVALID: u32 = 2;
+
+// Token type and state of unmapped input under `Unmapped::Unknown`.
+pub const UNKNOWN_NAME: &str = "Unknown";
+const UNKNOWN: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Utf8Unit {
+        Char(char),
+        Invalid(u8)
+}
+
+// Decodes UTF-8 a byte at a time. Each byte of a malformed or truncated
+// sequence comes out as its own `Invalid` unit.
+#[derive(Default)]
+struct Utf8Decoder {
+        buf: [u8; 4],
+        len: usize,
+        need: usize
+}
 
-                        if raw_bytes.is_empty() {
-                                tokens.push(Token { 
-                                        t: curr_byte_t.to_owned(), 
-                                        s: new_state.to_owned(),
-                                        value: curr_token.to_owned() });
+impl Utf8Decoder {
+        // Writes the units the byte completes to `out`, returning how many.
+        fn push(&mut self, b: u8, out: &mut [Utf8Unit; 4]) -> usize {
+                if self.len > 0 {
+                        if b & 0xc0 != 0x80 {
+                                let n = self.finish(out);
+                                return n + self.start(b, &mut out[n..]);
                         }
+                        self.buf[self.len] = b;
+                        self.len += 1;
+                        if self.len < self.need {
+                                return 0;
+                        }
+                        let n = match ::std::str::from_utf8(&self.buf[..self.len]) {
+                                Ok(s) => {
+                                        out[0] = Utf8Unit::Char(s.chars().next().unwrap());
+                                        1
+                                },
+                                Err(_) => {
+                                        for (o, &b) in out.iter_mut().zip(&self.buf[..self.len]) {
+                                                *o = Utf8Unit::Invalid(b);
+                                        }
+                                        self.len
+                                }
+                        };
+                        self.len = 0;
+                        return n;
+                }
+                self.start(b, out)
+        }
+        fn start(&mut self, b: u8, out: &mut [Utf8Unit]) -> usize {
+                self.need = match b {
+                        0x00..=0x7f => {
+                                out[0] = Utf8Unit::Char(b as char);
+                                return 1;
+                        },
+                        0xc2..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        0xf0..=0xf4 => 4,
+                        _ => {
+                                out[0] = Utf8Unit::Invalid(b);
+                                return 1;
+                        }
+                };
+                self.buf[0] = b;
+                self.len = 1;
+                0
+        }
+        // Flushes a sequence cut short, as invalid bytes.
+        fn finish(&mut self, out: &mut [Utf8Unit]) -> usize {
+                let n = self.len;
+                for (o, &b) in out.iter_mut().zip(&self.buf[..n]) {
+                        *o = Utf8Unit::Invalid(b);
+                }
+                self.len = 0;
+                n
+        }
+}
+
+pub struct TokenStream<'a, R> {
+        tokenizer: &'a Tokenizer,
+        reader: R,
+        machine: Machine,
+        pending: VecDeque<Token>,
+        done: bool,
+        error: Option<io::Error>
+}
 
-                        last_byte_t = curr_byte_t;
-                        curr_state = new_state;
+impl<'a, R: BufRead> TokenStream<'a, R> {
+        // A read error ends the stream early; it is kept here rather than
+        // yielded so the stream can stay an iterator of plain tokens.
+        pub fn error(&self) -> Option<&io::Error> {
+                self.error.as_ref()
+        }
+        pub fn take_error(&mut self) -> Option<io::Error> {
+                self.error.take()
+        }
+        // Bytes dropped from the input read so far.
+        pub fn dropped(&self) -> usize {
+                self.machine.dropped
+        }
+        fn fill(&mut self) {
+                let mut spans = Vec::new();
+                let len = match self.reader.fill_buf() {
+                        Ok(buf) => {
+                                for &b in buf {
+                                        self.machine.push(self.tokenizer, b, &mut spans);
+                                }
+                                buf.len()
+                        },
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => return,
+                        Err(e) => {
+                                self.error = Some(e);
+                                self.done = true;
+                                return;
+                        }
                 };
-                tokens
+                // unmapped input under `Unmapped::Error` ends the stream like a
+                // read error, as an `InvalidData` error wrapping it
+                if len == 0 || self.machine.error.is_some() {
+                        self.machine.finish(self.tokenizer, &mut spans);
+                        self.done = true;
+                }
+                if let Some(e) = self.machine.error.take() {
+                        self.error = Some(io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+                self.reader.consume(len);
+                self.pending.extend(spans.into_iter().map(Span::into_token));
+        }
+}
+
+impl<'a, R: BufRead> Iterator for TokenStream<'a, R> {
+        type Item = Token;
+
+        fn next(&mut self) -> Option<Token> {
+                while self.pending.is_empty() && !self.done {
+                        self.fill();
+                }
+                self.pending.pop_front()
         }
 }
 
@@ -142,7 +1020,6 @@ impl Tokenizer {
 mod tests {
 
         use super::*;
-        use utils::*;
         use std::collections::HashMap;
 
 
@@ -185,11 +1062,12 @@ mod tests {
 
         #[test]
         fn test_compile_states() {
+                let mut symbols = Tokenizer::symbol_table();
 
-                let transition_map: HashMap<State, HashMap<TokenType, State>> = Tokenizer::compile_states(&TRANSITIONS);
+                let transition_map: HashMap<State, HashMap<TokenType, State>> = Tokenizer::compile_states(TRANSITIONS, &mut symbols).unwrap();
 
-                let test_state_alpha = State(utils::get_hash_val(b"Alpha"));
-                let test_token_type_alpha = TokenType(utils::get_hash_val(b"Alpha"));
+                let test_state_alpha = State(symbols.get(b"Alpha").unwrap().0);
+                let test_token_type_alpha = TokenType(symbols.get(b"Alpha").unwrap().0);
 
                 assert_eq!(transition_map[&test_state_alpha][&test_token_type_alpha], test_state_alpha);
         }
@@ -197,26 +1075,43 @@ mod tests {
 
         #[test]
         fn test_compile_tokens() {
+                let mut symbols = Tokenizer::symbol_table();
 
-                let token_map: HashMap<u8, TokenType> = Tokenizer::compile_tokens_ascii(&TOKENS);
-                let test_token_type_alpha = TokenType(utils::get_hash_val(b"Alpha"));
+                let token_map: HashMap<u8, TokenType> = Tokenizer::compile_tokens_ascii(TOKENS, &mut symbols).unwrap();
+                let test_token_type_alpha = TokenType(symbols.get(b"Alpha").unwrap().0);
 
                 assert_eq!(token_map[&65], test_token_type_alpha);
                 assert_eq!(token_map[&122], test_token_type_alpha);
         }
 
+        #[test]
+        fn spec_errors_have_positions() {
+                let e = Tokenizer::new("Alpha => 65..123\nNumber => 48..300", "Start => Alpha => Alpha\nAlpha => Alpha => Alpha").err().unwrap();
+
+                assert_eq!((e.line, e.column), (2, 15));
+                assert_eq!(e.message, "expected a byte value, found `300`");
+
+                let e = Tokenizer::new("Alpha => 65..123", "Start => Alpha => Alpha\nAlpha => Number => Alpha").err().unwrap();
+
+                assert_eq!(e.to_string(), "line 2, column 10: undefined token type `Number`");
+        }
+
         #[test]
         fn tokenize_test_1() {
 
-                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS).unwrap();
 
                 let bs = vec![97, 98, 99];
 
                 let tokenized = tokenizer.tokenize(&bs);
                 let test_token = Token { 
-                        t: TokenType(utils::get_hash_val(b"Alpha")),
-                        s: State(utils::get_hash_val(b"Alpha")),
-                        value: bs };
+                        t: tokenizer.token_type("Alpha").unwrap(),
+                        s: tokenizer.state("Alpha").unwrap(),
+                        value: bs,
+                        start: 0,
+                        end: 3,
+                        line: 1,
+                        column: 1 };
 
                 assert_eq!(tokenized[0], test_token);
         }
@@ -226,28 +1121,32 @@ mod tests {
 
                 let bs = b"foo/bar";
 
-                let mut hashed_dict: HashMap<u32, &str> = HashMap::new();
-                hashed_dict.insert(utils::get_hash_val(b"Alpha"), "Alpha");
-                hashed_dict.insert(utils::get_hash_val(b"Whitespace"), "Whitepace");
-                hashed_dict.insert(utils::get_hash_val(b"Slash"), "Slash");
-                hashed_dict.insert(utils::get_hash_val(b"Pos"), "Pos");
-
-                println!("{:?}", hashed_dict);
+                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS).unwrap();
 
                 let test_alpha = Token {
-                        t: TokenType(utils::get_hash_val(b"Alpha")),
-                        s: State(utils::get_hash_val(b"Alpha")),
-                        value: b"foo".to_vec() };
+                        t: tokenizer.token_type("Alpha").unwrap(),
+                        s: tokenizer.state("Alpha").unwrap(),
+                        value: b"foo".to_vec(),
+                        start: 0,
+                        end: 3,
+                        line: 1,
+                        column: 1 };
                 let test_slash = Token {
-                        t: TokenType(utils::get_hash_val(b"Slash")),
-                        s: State(utils::get_hash_val(b"Slash")),
-                        value: b"/".to_vec() };
+                        t: tokenizer.token_type("Slash").unwrap(),
+                        s: tokenizer.state("Slash").unwrap(),
+                        value: b"/".to_vec(),
+                        start: 3,
+                        end: 4,
+                        line: 1,
+                        column: 4 };
                 let test_pos = Token {
-                        t: TokenType(utils::get_hash_val(b"Alpha")),
-                        s: State(utils::get_hash_val(b"Pos")),
-                        value: b"bar".to_vec() };
-
-                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+                        t: tokenizer.token_type("Alpha").unwrap(),
+                        s: tokenizer.state("Pos").unwrap(),
+                        value: b"bar".to_vec(),
+                        start: 4,
+                        end: 7,
+                        line: 1,
+                        column: 5 };
 
                 let tokenized = tokenizer.tokenize(bs);
                 let test_tokens = vec![&test_alpha, &test_slash, &test_pos];
@@ -261,34 +1160,342 @@ mod tests {
 
         }
 
+        #[test]
+        fn tokens_display_names() {
+                let mut tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+
+                let tokenized = tokenizer.tokenize(b"foo/bar");
+
+                assert_eq!(tokenizer.state_name(&tokenized[2].s), "Pos");
+                assert_eq!(tokenizer.type_name(&tokenized[2].t), "Alpha");
+                assert_eq!(tokenizer.named(&tokenized[1].t).to_string(), "Slash");
+                assert_eq!(tokenizer.named(&tokenized[2]).to_string(), "Pos(Alpha) \"bar\" at 1:5");
+                assert_eq!(tokenizer.state("Nowhere"), None);
+
+                // `Word` and `Cord` differ only in the first letter, which
+                // the old name hash ignored
+                let mut tokenizer = Tokenizer::new("Word => 97..123\nCord => 65..91", "
+                        Start => Word => Word
+                        Start => Cord => Cord
+                        Word => Word => Word
+                        Cord => Cord => Cord
+                        ").unwrap();
+                let names: Vec<String> = tokenizer.tokenize(b"abCD").iter().map(|t| tokenizer.named(&t.s).to_string()).collect();
+
+                assert_eq!(names, vec!["Word", "Cord"]);
+        }
+
         #[test]
         fn tokenize_test_3() {
 
                 let bs = b"foo ^ foo";
 
+                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS).unwrap();
+
                 let test_alpha = Token {
-                        t: TokenType(utils::get_hash_val(b"Alpha")),
-                        s: State(utils::get_hash_val(b"Alpha")),
-                        value: b"foo".to_vec() };
+                        t: tokenizer.token_type("Alpha").unwrap(),
+                        s: tokenizer.state("Alpha").unwrap(),
+                        value: b"foo".to_vec(),
+                        start: 0,
+                        end: 3,
+                        line: 1,
+                        column: 1 };
                 let test_white = Token {
-                        t: TokenType(utils::get_hash_val(b"Whitespace")),
-                        s: State(utils::get_hash_val(b"Whitespace")),
-                        value: b"  ".to_vec() };
-
-                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+                        t: tokenizer.token_type("Whitespace").unwrap(),
+                        s: tokenizer.state("Whitespace").unwrap(),
+                        value: b"  ".to_vec(),
+                        start: 3,
+                        end: 6,
+                        line: 1,
+                        column: 4 };
 
                 let tokenized = tokenizer.tokenize(bs);
 
                 assert_eq!(tokenized.len(), 3);
                 assert_eq!(tokenized[0], test_alpha);
                 assert_eq!(tokenized[1], test_white);
-                assert_eq!(tokenized[2], test_alpha);
+                assert_eq!(tokenized[2].value, test_alpha.value);
+                assert_eq!((tokenized[2].start, tokenized[2].end), (6, 9));
+
+        }
+
+        #[test]
+        fn tokenize_lines_and_columns() {
+                let mut tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+
+                let tokenized = tokenizer.tokenize(b"foo\n  bar baz");
+                let spans: Vec<(usize, usize, usize, usize)> = tokenized.iter()
+                        .map(|t| (t.start, t.end, t.line, t.column))
+                        .collect();
+
+                assert_eq!(spans, vec![(0, 3, 1, 1), (3, 6, 1, 4), (6, 9, 2, 3), (9, 10, 2, 6), (10, 13, 2, 7)]);
+        }
+
+        #[test]
+        fn stream_matches_tokenize() {
+                let mut tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+                let input = BROWN_CA01.as_bytes();
+                let expected = tokenizer.tokenize(input);
+
+                for &capacity in &[1, 2, 7, 4096] {
+                        let reader = ::std::io::BufReader::with_capacity(capacity, input);
+                        let streamed: Vec<Token> = tokenizer.stream(reader).collect();
+
+                        assert_eq!(streamed, expected);
+                }
+                assert_eq!(tokenizer.stream(&b""[..]).count(), 0);
+        }
+
+        struct FailingReader(usize);
+
+        impl ::std::io::Read for FailingReader {
+                fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                        if self.0 == 0 {
+                                return Err(io::Error::other("disk on fire"));
+                        }
+                        self.0 -= 1;
+                        buf[0] = b'a';
+                        Ok(1)
+                }
+        }
+
+        #[test]
+        fn stream_records_read_errors() {
+                let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+                let mut stream = tokenizer.stream(::std::io::BufReader::new(FailingReader(3)));
+
+                assert!(stream.next().is_none());
+                assert_eq!(stream.take_error().unwrap().to_string(), "disk on fire");
+        }
+
+        #[test]
+        fn tokenize_ref_borrows_input() {
+                let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+                let bs = b"foo ^ bar/baz";
+
+                let tokens = tokenizer.tokenize_ref(bs);
+
+                assert_eq!(tokens.len(), 5);
+                assert!(tokens.iter().enumerate().all(|(i, t)| i == 1 || t.value == &bs[t.start..t.end]));
+                match tokens[0].value {
+                        Cow::Borrowed(v) => assert_eq!(v.as_ptr(), bs.as_ptr()),
+                        Cow::Owned(_) => panic!("expected a borrowed value")
+                }
+                // the dropped '^' splits the whitespace token
+                assert_eq!(tokens[1].value, Cow::Owned::<[u8]>(b"  ".to_vec()));
+                assert_eq!(tokens.into_iter().map(Token::from).collect::<Vec<Token>>(),
+                           Tokenizer::new(TOKENS, TRANSITIONS).unwrap().tokenize(bs));
+        }
+
+        static UNICODE_TOKENS: &str = "
+                Alpha => \\p{L}, \\p{M}
+                Number => \\p{Nd}
+                Whitespace => 9, 10, 13, 32
+                ";
+
+        static UNICODE_TRANSITIONS: &str = "
+                Start => Alpha => Alpha
+                Start => Number => Number
+                Start => Whitespace => Whitespace
+                Start => Invalid => Invalid
+                Alpha => Alpha => Alpha
+                Number => Number => Number
+                Whitespace => Whitespace => Whitespace
+                Invalid => Invalid => Invalid
+                ";
+
+        fn values(tokens: &[Token]) -> Vec<&str> {
+                tokens.iter().map(|t| ::std::str::from_utf8(&t.value).unwrap_or("<invalid>")).collect()
+        }
+
+        #[test]
+        fn tokenize_unicode() {
+                let mut tokenizer = Tokenizer::unicode(UNICODE_TOKENS, UNICODE_TRANSITIONS).unwrap();
+                let text = "naïve λόγος ٣٤".as_bytes();
+
+                let tokenized = tokenizer.tokenize(text);
+
+                assert_eq!(values(&tokenized), vec!["naïve", " ", "λόγος", " ", "٣٤"]);
+                assert_eq!((tokenized[2].start, tokenized[2].end), (7, 17));
+                assert_eq!(tokenized[4].s, tokenizer.state("Number").unwrap());
+
+                for &capacity in &[1, 3] {
+                        let reader = ::std::io::BufReader::with_capacity(capacity, text);
+                        assert_eq!(tokenizer.stream(reader).collect::<Vec<Token>>(), tokenized);
+                }
+        }
+
+        #[test]
+        fn tokenize_invalid_utf8() {
+                let mut tokenizer = Tokenizer::unicode(UNICODE_TOKENS, UNICODE_TRANSITIONS).unwrap();
+
+                let tokenized = tokenizer.tokenize(b"ab\xff\xfecd \xce");
+
+                assert_eq!(tokenized.len(), 5);
+                assert_eq!(tokenized[1].value, vec![0xff, 0xfe]);
+                assert_eq!(tokenized[1].s, tokenizer.state(INVALID_NAME).unwrap());
+                // a sequence cut off by the end of the input is invalid too
+                assert_eq!(tokenized[4].value, vec![0xce]);
+
+                // without transitions for Invalid, invalid bytes end the
+                // token they interrupt and are dropped
+                let mut dropping = Tokenizer::unicode(UNICODE_TOKENS, "
+                        Start => Alpha => Alpha
+                        Start => Whitespace => Whitespace
+                        Alpha => Alpha => Alpha
+                        Whitespace => Whitespace => Whitespace
+                        ").unwrap();
+                let tokenized = dropping.tokenize(b"ab\xffcd");
+
+                assert_eq!(values(&tokenized), vec!["ab", "cd"]);
+                assert_eq!((tokenized[1].start, tokenized[1].end), (3, 5));
+        }
+
+        #[test]
+        fn unmapped_policies() {
+                let text = b"ab^^cd e";
+                let tokenize = |policy| {
+                        let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().unmapped(policy);
+                        let tokenized = tokenizer.try_tokenize(text).unwrap();
+                        (values(&tokenized.tokens).join("|"), tokenized.dropped)
+                };
+
+                assert_eq!(tokenize(Unmapped::Drop), (String::from("abcd| |e"), 2));
+                assert_eq!(tokenize(Unmapped::Separator), (String::from("ab|cd| |e"), 2));
+                assert_eq!(tokenize(Unmapped::Unknown), (String::from("ab|^^|cd| |e"), 0));
+
+                let mut tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap();
+                tokenizer.tokenize(text);
+
+                assert_eq!(tokenizer.dropped(), 2);
+                tokenizer.tokenize(b"ab cd");
+                assert_eq!(tokenizer.dropped(), 0);
+
+                let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().unmapped(Unmapped::Unknown);
+                let tokens = tokenizer.tokenize_ref(text);
+
+                assert_eq!(tokens[1].t, tokenizer.token_type(UNKNOWN_NAME).unwrap());
+                assert_eq!((tokens[1].start, tokens[1].end), (2, 4));
+                let reader = ::std::io::BufReader::with_capacity(3, &text[..]);
+                assert_eq!(tokenizer.stream(reader).collect::<Vec<Token>>(), tokens.into_iter().map(Token::from).collect::<Vec<Token>>());
+
+                let tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().unmapped(Unmapped::Error);
+                let e = tokenizer.try_tokenize(text).unwrap_err();
+
+                assert_eq!((e.offset, e.line, e.column), (2, 1, 3));
+                assert_eq!(e.to_string(), "no token type for byte 0x5e at line 1, column 3 (offset 2)");
+                assert_eq!(values(&tokenizer.try_tokenize(b"ab cd").unwrap().tokens), vec!["ab", " ", "cd"]);
+
+                let mut stream = tokenizer.stream(&text[..]);
+
+                assert_eq!(stream.by_ref().map(|t| t.value).collect::<Vec<Vec<u8>>>(), vec![b"ab".to_vec()]);
+                assert_eq!(stream.take_error().unwrap().kind(), io::ErrorKind::InvalidData);
+
+                let rules = Tokenizer::regex("Word = [a-z]+").unwrap();
+
+                assert_eq!(rules.try_tokenize(b"ab^^cd").unwrap().dropped, 2);
+
+                let rules = rules.unmapped(Unmapped::Unknown);
+
+                assert_eq!(values(&rules.try_tokenize(b"ab^^cd^").unwrap().tokens), vec!["ab", "^^", "cd", "^"]);
+        }
+
+        #[test]
+        #[should_panic(expected = "no token type for byte 0x5e at line 1, column 3")]
+        fn tokenize_panics_on_unmapped_error() {
+                let mut tokenizer = Tokenizer::new(TOKENS, TRANSITIONS).unwrap().unmapped(Unmapped::Error);
+                tokenizer.tokenize(b"ab^^cd e");
+        }
+
+        #[test]
+        fn tokenize_regex_rules() {
+                let mut tokenizer = Tokenizer::regex("
+                        Url = https?://[^ ]+
+                        Email = [\\w.]+@\\w+(\\.\\w+)+
+                        Word = [A-Za-z]+('[a-z]+)?
+                        Decimal = \\d+(\\.\\d+)?
+                        Space = \\s+
+                        ").unwrap();
+                let text = b"Don't pay 3.50, mail a.b@c.org\nor see http://x.org/y ~";
+
+                let tokenized = tokenizer.tokenize(text);
+
+                assert_eq!(values(&tokenized), vec![
+                        "Don't", " ", "pay", " ", "3.50", " ", "mail", " ", "a.b@c.org", "\n",
+                        "or", " ", "see", " ", "http://x.org/y", " "]);
+                assert_eq!(tokenizer.type_name(&tokenized[8].t), "Email");
+                assert_eq!(tokenizer.state_name(&tokenized[14].s), "Url");
+                assert_eq!((tokenized[10].start, tokenized[10].line, tokenized[10].column), (31, 2, 1));
+
+                // "3." backs off to the number before the dot, which is dropped
+                let tokenized = tokenizer.tokenize(b"3.x");
+
+                assert_eq!(values(&tokenized), vec!["3", "x"]);
+                assert_eq!(tokenized[1].start, 2);
+
+                let borrowed = tokenizer.tokenize_ref(text);
+                assert!(borrowed.iter().all(|t| matches!(t.value, Cow::Borrowed(_))));
+                for &capacity in &[1, 4] {
+                        let reader = ::std::io::BufReader::with_capacity(capacity, &text[..]);
+                        assert_eq!(tokenizer.stream(reader).collect::<Vec<Token>>(), tokenizer.tokenize(text));
+                }
+        }
+
+        #[test]
+        fn standard_specs() {
+                // the tables `Corpus::brown` had inline
+                let tokens = "Alpha => 65..123\nNumber => 48..57\nWhitespace => 9,10,13,32\n\
+                        Punctuation => 33..46\nPunctuation => 58..65\nSlash => 47\n";
+                let mut brown = Tokenizer::standard("brown").unwrap();
+                let mut inline = Tokenizer::new(tokens, TRANSITIONS).unwrap();
+
+                let tokenized = brown.tokenize(BROWN_CA01.as_bytes());
+                let expected = inline.tokenize(BROWN_CA01.as_bytes());
+                let names = |tokenizer: &Tokenizer, tokens: &[Token]| -> Vec<String> {
+                        tokens.iter().map(|t| tokenizer.named(t).to_string()).collect()
+                };
+
+                assert_eq!(names(&brown, &tokenized), names(&inline, &expected));
+
+                let mut english = Tokenizer::standard("english").unwrap();
+                let tokenized = english.tokenize(b"It's 1,000.5 well-known");
+
+                assert_eq!(values(&tokenized), vec!["It's", " ", "1,000.5", " ", "well-known"]);
+
+                let mut csv = Tokenizer::standard("csv").unwrap();
+                let tokenized = csv.tokenize(b"a,\"b,\"\"c\"\"\"\r\n");
+
+                assert_eq!(values(&tokenized), vec!["a", ",", "\"b,\"\"c\"\"\"", "\r\n"]);
+        }
+
+        #[test]
+        fn spec_files_with_includes() {
+                let dir = ::std::env::temp_dir().join(format!("graffiti-specs-{}", ::std::process::id()));
+                ::std::fs::create_dir_all(&dir).unwrap();
+                ::std::fs::write(dir.join("letters.spec"), "# shared\nAlpha => 97..123\n").unwrap();
+                ::std::fs::write(dir.join("words.spec"), "[tokens]\ninclude letters\nSpace => 32\n\n[transitions]\n\
+                        Start => Alpha => Alpha\nStart => Space => Space\nAlpha => Alpha => Alpha\nSpace => Space => Space\n").unwrap();
+                ::std::fs::write(dir.join("broken.spec"), "[tokens]\ninclude letters\n[transitions]\nStart => Alpah => Alpha\n").unwrap();
+
+                let mut tokenizer = Tokenizer::from_file(dir.join("words.spec")).unwrap();
+
+                assert_eq!(values(&tokenizer.tokenize(b"ab cd")), vec!["ab", " ", "cd"]);
+
+                let e = match Tokenizer::from_file(dir.join("broken.spec")) {
+                        Err(LoadError::Spec(e)) => e,
+                        _ => panic!("expected a spec error")
+                };
+
+                assert_eq!(e.file, Some(dir.join("broken.spec").display().to_string()));
+                assert_eq!((e.line, e.column), (4, 10));
+                assert!(Tokenizer::from_reader(&b"[tokens]\ninclude letters\n"[..]).is_err());
 
+                ::std::fs::remove_dir_all(&dir).unwrap();
         }
 
         #[test]
         fn tokenize_test_4() {
-                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS);
+                let mut tokenizer = Tokenizer::new(&TOKENS, &TRANSITIONS).unwrap();
 
                 let tokenized = tokenizer.tokenize(&BROWN_CA01.as_bytes().to_vec());
 
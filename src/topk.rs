@@ -0,0 +1,187 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use postings::*;
+use scoring::*;
+
+// One query term's postings, walked in doc id order during WAND.
+pub struct TermCursor<'a> {
+    pub docs: &'a [u32],
+    pub tfs: &'a [u32],
+    pub qtf: u32,
+    pub term: TermStats,
+    // no document can get more than this from the term
+    pub upper_bound: f64,
+    at: usize,
+}
+
+impl<'a> TermCursor<'a> {
+    pub fn new(docs: &'a [u32], tfs: &'a [u32], qtf: u32, term: TermStats, upper_bound: f64) -> Self {
+        TermCursor {
+            docs,
+            tfs,
+            qtf,
+            term,
+            upper_bound,
+            at: 0
+        }
+    }
+    fn doc(&self) -> Option<u32> {
+        self.docs.get(self.at).cloned()
+    }
+    fn advance_to(&mut self, doc: u32) {
+        self.at = gallop(self.docs, self.at, doc);
+    }
+}
+
+struct Hit(f64, u32);
+
+impl PartialEq for Hit {
+    fn eq(&self, other: &Hit) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Hit {}
+
+impl PartialOrd for Hit {
+    fn partial_cmp(&self, other: &Hit) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so the BinaryHeap keeps the worst hit on top; on equal scores
+// the higher doc id counts as worse.
+impl Ord for Hit {
+    fn cmp(&self, other: &Hit) -> Ordering {
+        cmp_scores(other.0, self.0).then(self.1.cmp(&other.1))
+    }
+}
+
+pub struct TopK {
+    k: usize,
+    heap: BinaryHeap<Hit>,
+}
+
+impl TopK {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1)
+        }
+    }
+    // Score a document needs to beat to make it into the results.
+    pub fn threshold(&self) -> f64 {
+        if self.heap.len() < self.k {
+            return 0.0;
+        }
+        self.heap.peek().map_or(0.0, |h| h.0)
+    }
+    pub fn push(&mut self, doc: u32, score: f64) {
+        self.heap.push(Hit(score, doc));
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+    pub fn into_sorted_vec(self) -> Vec<(u32,f64)> {
+        self.heap.into_sorted_vec().into_iter().map(|h| (h.1, h.0)).collect()
+    }
+}
+
+// WAND (Broder et al. 2003). Cursors are kept ordered by current doc; the
+// pivot is the first doc whose preceding upper bounds could beat the current
+// threshold, and every cursor before it skips straight to the pivot, so
+// documents that cannot enter the top k are never scored.
+pub fn wand<F>(mut cursors: Vec<TermCursor>, k: usize, mut score: F) -> Vec<(u32,f64)>
+    where F: FnMut(u32, &[(u32, u32, TermStats)]) -> f64
+{
+    let mut top = TopK::new(k);
+    if k == 0 {
+        return Vec::new();
+    }
+    loop {
+        cursors.retain(|c| c.doc().is_some());
+        cursors.sort_by_key(|c| c.doc());
+        let threshold = top.threshold();
+        let mut bound = 0.0;
+        let mut pivot = None;
+        for (i, c) in cursors.iter().enumerate() {
+            bound += c.upper_bound;
+            if bound > threshold {
+                pivot = Some(i);
+                break;
+            }
+        }
+        let pivot_doc = match pivot {
+            Some(i) => cursors[i].doc().unwrap(),
+            None => break
+        };
+        if cursors[0].doc() == Some(pivot_doc) {
+            let matched: Vec<(u32, u32, TermStats)> = cursors.iter()
+                .take_while(|c| c.doc() == Some(pivot_doc))
+                .map(|c| (c.tfs[c.at], c.qtf, c.term))
+                .collect();
+            let s = score(pivot_doc, &matched);
+            if top.heap.len() < k || s > threshold {
+                top.push(pivot_doc, s);
+            }
+            for c in cursors.iter_mut().take(matched.len()) {
+                c.at += 1;
+            }
+        } else {
+            for c in cursors.iter_mut().take_while(|c| c.doc() != Some(pivot_doc)) {
+                c.advance_to(pivot_doc);
+            }
+        }
+    }
+    top.into_sorted_vec()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn top_k_keeps_best() {
+        let mut top = TopK::new(2);
+
+        top.push(1, 0.5);
+        top.push(2, 2.0);
+        top.push(3, 1.0);
+        top.push(4, 0.1);
+
+        assert_eq!(top.threshold(), 1.0);
+        assert_eq!(top.into_sorted_vec(), vec![(2, 2.0), (3, 1.0)]);
+    }
+
+    #[test]
+    fn top_k_drops_nan_first() {
+        let mut top = TopK::new(2);
+
+        top.push(1, f64::NAN);
+        top.push(2, 2.0);
+        top.push(3, f64::NAN);
+        top.push(4, 1.0);
+        top.push(5, 0.5);
+
+        assert_eq!(top.threshold(), 1.0);
+        assert_eq!(top.into_sorted_vec(), vec![(2, 2.0), (4, 1.0)]);
+    }
+
+    #[test]
+    fn wand_skips_hopeless_docs() {
+        let term = TermStats { df: 3, cf: 3 };
+        let rare = TermCursor::new(&[5, 9], &[1, 1], 1, term, 10.0);
+        let common = TermCursor::new(&[1, 2, 3, 4, 5, 6, 7, 8, 9], &[1; 9], 1, term, 1.0);
+        let mut scored = Vec::new();
+
+        let top = wand(vec![rare, common], 1, |d, m| {
+            scored.push(d);
+            m.iter().map(|&(tf, _, _)| tf as f64 * if d % 2 == 1 { 10.0 } else { 1.0 }).sum()
+        });
+
+        assert_eq!(top[0].0, 5);
+        assert!(scored.len() < 9);
+    }
+}